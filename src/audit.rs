@@ -0,0 +1,174 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AuditConfig;
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::html::{HtmlParser, HtmlTag};
+use crate::utils::Result;
+
+/// How serious a finding is; ordered from least to most severe
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    #[default]
+    Error,
+}
+
+/// Problem detected by an audit rule
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Finding {
+    rule: String,
+    severity: Severity,
+    message: String,
+}
+
+/// Checks documents against the configured thresholds
+pub struct Audit<'a> {
+    rules: &'a AuditConfig,
+}
+
+// === impl Severity ===
+
+impl Severity {
+    pub const VARIANTS: [&'static str; 3] = ["info", "warning", "error"];
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(Error::new(ErrorType::Message(
+                ErrorKind::InvalidParameters,
+                format!("unknown severity {}", s),
+            ))),
+        }
+    }
+}
+
+// === impl Finding ===
+
+impl Finding {
+    pub fn new(rule: &str, severity: Severity, message: String) -> Finding {
+        Finding {
+            rule: rule.to_string(),
+            severity,
+            message,
+        }
+    }
+
+    pub fn rule(&self) -> &str {
+        &self.rule
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+// === impl Audit ===
+
+impl<'a> Audit<'a> {
+    pub fn new(rules: &'a AuditConfig) -> Audit<'a> {
+        Audit { rules }
+    }
+
+    /// Runs every rule against the parser
+    pub fn run<P: HtmlParser>(&self, parser: &P) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        self.text(
+            &mut findings,
+            "title",
+            parser.page_title()?,
+            self.rules.title_min_length,
+            self.rules.title_max_length,
+        );
+        self.text(
+            &mut findings,
+            "description",
+            parser.descriptions()?,
+            self.rules.description_min_length,
+            self.rules.description_max_length,
+        );
+
+        let h1 = parser.header(HtmlTag::H1)?.len();
+        if h1 == 0 {
+            findings.push(Finding::new(
+                "missing-h1",
+                Severity::Warning,
+                "page has no h1".to_string(),
+            ));
+        } else if h1 > self.rules.max_h1 {
+            findings.push(Finding::new(
+                "multiple-h1",
+                Severity::Warning,
+                format!("page has {} h1 headers; expected at most {}", h1, self.rules.max_h1),
+            ));
+        }
+        Ok(findings)
+    }
+
+    /// Checks an element expected exactly once with text length within bounds
+    fn text(&self, findings: &mut Vec<Finding>, name: &str, values: Vec<String>, min: usize, max: usize) {
+        let values = values
+            .iter()
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>();
+        let value = match values.as_slice() {
+            [] => {
+                findings.push(Finding::new(
+                    &format!("missing-{}", name),
+                    Severity::Error,
+                    format!("page has no {}", name),
+                ));
+                return;
+            }
+            [value] => value,
+            [value, ..] => {
+                findings.push(Finding::new(
+                    &format!("multiple-{}", name),
+                    Severity::Warning,
+                    format!("page has {} {}s", values.len(), name),
+                ));
+                value
+            }
+        };
+
+        let len = value.chars().count();
+        if len < min {
+            findings.push(Finding::new(
+                &format!("{}-too-short", name),
+                Severity::Warning,
+                format!("{} is {} characters; expected at least {}", name, len, min),
+            ));
+        } else if len > max {
+            findings.push(Finding::new(
+                &format!("{}-too-long", name),
+                Severity::Warning,
+                format!("{} is {} characters; expected at most {}", name, len, max),
+            ));
+        }
+    }
+}
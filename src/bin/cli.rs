@@ -1,21 +1,65 @@
 #![allow(unused)]
 use inquest::cli::Cli;
+use inquest::data::Json;
+use inquest::error::{Error, ErrorKind};
+use inquest::exit::ExitCode;
+use inquest::output::Output;
+use inquest::report::Report;
 use inquest::system::System;
+use tokio::sync::mpsc;
 use tracing::Level;
-use tracing_subscriber;
-use inquest::data::Json;
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt()
         .with_max_level(Level::TRACE)
+        .with_writer(std::io::stderr)
         .compact()
         .init();
 
-    let cli = Cli::init();
-    let srv = Cli::service();
+    let code = match run().await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(&err)
+        }
+    };
+    std::process::exit(code.code());
+}
+
+async fn run() -> Result<ExitCode, Error> {
+    let mut cli = match Cli::init() {
+        Ok(cli) => cli,
+        Err(err) if err.use_stderr() => {
+            eprintln!("{}", err.message);
+            return Ok(ExitCode::Usage);
+        }
+        // help and version
+        Err(err) => {
+            println!("{}", err.message);
+            return Ok(ExitCode::Success);
+        }
+    };
+    cli.load_config()?;
+    let fail_on = cli.fail_on();
+    let mut output = Output::create(cli.format(), cli.output())?;
+    let (tx, mut rx) = mpsc::unbounded_channel::<Report>();
+
+    // reports are written as soon as each target completes
+    let writer = tokio::spawn(async move {
+        let mut code = ExitCode::Success;
+        while let Some(report) = rx.recv().await {
+            output.write(&report)?;
+            code = code.max(ExitCode::from_report(&report, fail_on));
+        }
+        output.finish().map(|_| code)
+    });
 
-    let json = Json::new(cli);
+    let srv = cli.service(tx)?;
+    let res = System::bind(srv).run(Json::new(&cli)?).await;
 
-    System::bind(srv).run(json).await;
+    match writer.await {
+        Ok(written) => res.and(written),
+        Err(_) => Err(Error::from(ErrorKind::Failed)),
+    }
 }
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+
+use futures::future::{self, BoxFuture, FutureExt, Shared};
+use reqwest::{Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tracing::{event, Level};
+
+use crate::html::{Link, LinkKind};
+use crate::http::{self, FetchError, Fetched, ReadBody};
+use crate::logging::CLI;
+
+/// Requests sent to a single host at once when not specified
+pub const DEFAULT_PER_HOST: usize = 2;
+
+/// Statuses of servers not supporting HEAD; the link is requested again with GET
+pub const HEAD_REFUSED: [StatusCode; 2] =
+    [StatusCode::METHOD_NOT_ALLOWED, StatusCode::NOT_IMPLEMENTED];
+
+/// What checking a link turned up
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkOutcome {
+    /// The link answered with a success status or the file exists
+    Ok,
+    /// The server answered with a client or server error status
+    Broken,
+    /// The linked file does not exist on disk
+    Missing,
+    Timeout,
+    Dns,
+    Connection,
+    /// The request failed for any other reason
+    Failed,
+    /// Not checked; anchors and non http(s) or file links
+    Skipped,
+}
+
+/// Result of checking a single link
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LinkCheck {
+    url: String,
+    kind: LinkKind,
+    status: Option<u16>,
+    redirects: Vec<String>,
+    outcome: LinkOutcome,
+    reason: Option<String>,
+}
+
+/// Checks links, requesting each url only once however many pages link to it
+///
+/// Clones share the same results and per host limits.
+#[derive(Clone)]
+pub struct LinkChecker {
+    client: http::Client,
+    per_host: usize,
+    checked: Arc<Mutex<HashMap<String, Shared<BoxFuture<'static, LinkCheck>>>>>,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+// === impl LinkOutcome ===
+
+impl LinkOutcome {
+    pub fn is_broken(&self) -> bool {
+        !matches!(self, LinkOutcome::Ok | LinkOutcome::Skipped)
+    }
+}
+
+impl Display for LinkOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkOutcome::Ok => write!(f, "ok"),
+            LinkOutcome::Broken => write!(f, "broken"),
+            LinkOutcome::Missing => write!(f, "missing"),
+            LinkOutcome::Timeout => write!(f, "timeout"),
+            LinkOutcome::Dns => write!(f, "dns"),
+            LinkOutcome::Connection => write!(f, "connection"),
+            LinkOutcome::Failed => write!(f, "failed"),
+            LinkOutcome::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+// === impl LinkCheck ===
+
+impl LinkCheck {
+    fn new(outcome: LinkOutcome) -> LinkCheck {
+        LinkCheck {
+            url: String::new(),
+            kind: LinkKind::Other,
+            status: None,
+            redirects: Vec::new(),
+            outcome,
+            reason: None,
+        }
+    }
+
+    fn with_reason(mut self, reason: String) -> LinkCheck {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn kind(&self) -> LinkKind {
+        self.kind
+    }
+
+    /// Status of the final response, after following any redirects
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// Urls the link was redirected to, in order; the last one is where it
+    /// ends up
+    pub fn redirects(&self) -> &[String] {
+        &self.redirects
+    }
+
+    pub fn outcome(&self) -> LinkOutcome {
+        self.outcome
+    }
+
+    /// Why the link is broken or was skipped
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.outcome.is_broken()
+    }
+}
+
+// === impl LinkChecker ===
+
+impl LinkChecker {
+    pub fn new(client: http::Client, per_host: usize) -> LinkChecker {
+        LinkChecker {
+            client,
+            per_host: per_host.max(1),
+            checked: Arc::new(Mutex::new(HashMap::new())),
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Checks every link, in order
+    pub async fn check_all(&self, links: &[Link]) -> Vec<LinkCheck> {
+        future::join_all(links.iter().map(|link| self.check(link))).await
+    }
+
+    pub async fn check(&self, link: &Link) -> LinkCheck {
+        let mut check = match (link.kind(), Url::parse(link.url()).ok()) {
+            (LinkKind::Internal | LinkKind::External, Some(mut url)) => {
+                url.set_fragment(None);
+                self.shared(url).await
+            }
+            (LinkKind::Internal, None) => LinkCheck::new(LinkOutcome::Skipped)
+                .with_reason("relative link without a base url".to_string()),
+            _ => LinkCheck::new(LinkOutcome::Skipped),
+        };
+        check.url = link.url().to_string();
+        check.kind = link.kind();
+        check
+    }
+
+    /// Pending or completed check for `url`, started if nobody asked for it yet
+    fn shared(&self, url: Url) -> Shared<BoxFuture<'static, LinkCheck>> {
+        let mut checked = self.checked.lock().unwrap();
+        if let Some(check) = checked.get(url.as_str()) {
+            return check.clone();
+        }
+
+        let key = url.to_string();
+        let check = match url.scheme() {
+            "file" => LinkChecker::file(url).boxed().shared(),
+            "http" | "https" => {
+                let limit = self.limit(&url);
+                LinkChecker::http(self.client.clone(), limit, url).boxed().shared()
+            }
+            _ => future::ready(LinkCheck::new(LinkOutcome::Skipped)).boxed().shared(),
+        };
+        checked.insert(key, check.clone());
+        check
+    }
+
+    fn limit(&self, url: &Url) -> Arc<Semaphore> {
+        let host = url.host_str().unwrap_or_default().to_string();
+        self.hosts
+            .lock()
+            .unwrap()
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_host)))
+            .clone()
+    }
+
+    async fn file(url: Url) -> LinkCheck {
+        let path = match url.to_file_path() {
+            Ok(path) => path,
+            Err(_) => {
+                return LinkCheck::new(LinkOutcome::Missing).with_reason("invalid file path".to_string())
+            }
+        };
+        match tokio::fs::metadata(&path).await {
+            Ok(_) => LinkCheck::new(LinkOutcome::Ok),
+            Err(err) => LinkCheck::new(LinkOutcome::Missing).with_reason(err.to_string()),
+        }
+    }
+
+    async fn http(client: http::Client, limit: Arc<Semaphore>, url: Url) -> LinkCheck {
+        // the semaphore is never closed
+        let _permit = limit.acquire_owned().await;
+        event!(target: CLI, Level::DEBUG, "checking link; url={}", url);
+
+        // some servers refuse HEAD outright; only their answer is confirmed with GET
+        let head = client.send(Method::HEAD, url.as_str(), ReadBody::Nothing).await;
+        let fetched = match head {
+            Ok(fetched) if HEAD_REFUSED.contains(&fetched.meta.status_code()) => {
+                client.send(Method::GET, url.as_str(), ReadBody::Nothing).await
+            }
+            head => head,
+        };
+
+        match fetched {
+            Ok(Fetched { meta, .. }) => {
+                let status = meta.status_code();
+                let outcome = match status {
+                    s if s.is_client_error() || s.is_server_error() => LinkOutcome::Broken,
+                    _ => LinkOutcome::Ok,
+                };
+                let mut check = LinkCheck::new(outcome);
+                check.status = Some(status.as_u16());
+                if !meta.redirects().is_empty() {
+                    check.redirects = meta
+                        .redirects()
+                        .iter()
+                        .skip(1)
+                        .map(String::as_str)
+                        .chain(std::iter::once(meta.url()))
+                        .map(str::to_string)
+                        .collect();
+                }
+                if outcome == LinkOutcome::Broken {
+                    check.reason = status.canonical_reason().map(str::to_string);
+                }
+                check
+            }
+            Err(err) => {
+                let outcome = match err {
+                    FetchError::Timeout(_) => LinkOutcome::Timeout,
+                    FetchError::Dns(_) => LinkOutcome::Dns,
+                    FetchError::Connection(_) => LinkOutcome::Connection,
+                    _ => LinkOutcome::Failed,
+                };
+                LinkCheck::new(outcome).with_reason(err.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use select::predicate::Name;
+
+    use super::*;
+    use crate::html::{Html, HtmlParser};
+    use crate::http::{Client, MemoryTransport, Reply, Transport};
+
+    /// Answers from memory, remembering every request sent
+    #[derive(Debug, Default)]
+    struct Spy {
+        /// Replies to HEAD requests, when they differ from those to GET
+        head: MemoryTransport,
+        replies: MemoryTransport,
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Transport for Spy {
+        async fn send(
+            &self,
+            method: &Method,
+            url: &Url,
+            credentials: bool,
+            body: ReadBody,
+        ) -> Result<http::Reply, FetchError> {
+            self.sent.lock().unwrap().push(format!("{} {}", method, url));
+            match self.head.send(method, url, credentials, body).await {
+                Ok(reply) if method == Method::HEAD => Ok(reply),
+                _ => self.replies.send(method, url, credentials, body).await,
+            }
+        }
+    }
+
+    fn page(body: &str) -> Reply {
+        Reply::new(200).header("Content-Type", "text/html").body(body)
+    }
+
+    /// Checks every link of `html`, read from `url`, along with the requests
+    /// sent, sorted as links are checked concurrently
+    async fn check(spy: Spy, html: &str, url: Option<&str>) -> (Vec<LinkCheck>, Vec<String>) {
+        let sent = spy.sent.clone();
+        let checker = LinkChecker::new(Client::new(spy), DEFAULT_PER_HOST);
+        let links = Html::inline(html, url).links(Name("a")).unwrap();
+        let checks = checker.check_all(&links).await;
+        let mut sent = sent.lock().unwrap().clone();
+        sent.sort();
+        (checks, sent)
+    }
+
+    #[tokio::test]
+    async fn links_refusing_head_are_requested_again_with_get() {
+        let spy = Spy {
+            head: MemoryTransport::new()
+                .reply("http://site.test/legacy", Reply::new(405))
+                .reply("http://site.test/gone", Reply::new(404)),
+            replies: MemoryTransport::new()
+                .reply("http://site.test/legacy", page("still here"))
+                .reply("http://site.test/gone", page("never asked for")),
+            ..Spy::default()
+        };
+        let html = "<a href=/legacy>legacy</a><a href=/gone>gone</a>";
+        let (checks, sent) = check(spy, html, Some("http://site.test/")).await;
+
+        assert_eq!(checks[0].outcome(), LinkOutcome::Ok);
+        assert_eq!(checks[0].status(), Some(200));
+        // only a refused HEAD is confirmed with GET
+        assert_eq!(checks[1].outcome(), LinkOutcome::Broken);
+        assert_eq!(checks[1].status(), Some(404));
+        assert_eq!(checks[1].reason(), Some("Not Found"));
+        assert_eq!(
+            sent,
+            [
+                "GET http://site.test/legacy",
+                "HEAD http://site.test/gone",
+                "HEAD http://site.test/legacy",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn links_to_the_same_url_are_requested_once() {
+        let spy = Spy {
+            replies: MemoryTransport::new().reply("http://site.test/a", page("a")),
+            ..Spy::default()
+        };
+        let html = "<a href=/a>a</a><a href=a#top>top</a><a href='http://site.test/a'>a</a>";
+        let (checks, sent) = check(spy, html, Some("http://site.test/")).await;
+
+        let urls = checks.iter().map(LinkCheck::url).collect::<Vec<_>>();
+        assert_eq!(urls, ["http://site.test/a", "http://site.test/a#top", "http://site.test/a"]);
+        assert!(checks.iter().all(|check| check.outcome() == LinkOutcome::Ok));
+        assert_eq!(sent, ["HEAD http://site.test/a"]);
+    }
+
+    #[tokio::test]
+    async fn relative_links_without_a_base_are_skipped() {
+        let html = "<a href=a.html>about</a><a href=#top>top</a><a href=mailto:me@a.test>me</a>";
+        let (checks, sent) = check(Spy::default(), html, None).await;
+
+        let checks = checks
+            .iter()
+            .map(|check| (check.url(), check.kind(), check.outcome(), check.reason()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            [
+                (
+                    "a.html",
+                    LinkKind::Internal,
+                    LinkOutcome::Skipped,
+                    Some("relative link without a base url")
+                ),
+                ("#top", LinkKind::Anchor, LinkOutcome::Skipped, None),
+                ("mailto:me@a.test", LinkKind::Mailto, LinkOutcome::Skipped, None),
+            ]
+        );
+        assert!(sent.is_empty());
+    }
+}
@@ -9,26 +9,52 @@ use hyper::Body;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::path::PathBuf;
+use structopt::clap;
 use structopt::StructOpt;
+use tower::steer::Steer;
 use tower::util::BoxService;
 use tower::ServiceBuilder;
 use tracing::{event, Level};
+use crate::cli::services::crawl::CrawlService;
 use crate::cli::services::html::HtmlOptsLayer;
+use crate::cli::services::probe::ProbeService;
+use crate::audit::Severity;
+use crate::config::{AuditConfig, Config};
 use crate::data::Json;
+use crate::extract::ExtractRule;
+use crate::html::Selection;
+use crate::http::HttpOpts;
+use crate::output::Format;
+use crate::report::Report;
+use crate::utils::Reporter;
 
 pub mod services;
 
-#[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
+#[derive(StructOpt, Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
 pub struct HtmlParseOpts {
-    /// Filter based on HTML tag
+    /// Filter based on HTML tag; header levels (1-6) for `headers`, or the
+    /// elements links must be nested within for `links`
     #[structopt(short, long)]
     pub tags: Option<Vec<String>>,
-    /// File paths to be probed
+    /// File paths to be probed; `-` reads the document from stdin
     #[structopt(parse(from_os_str), short, long)]
     pub paths: Option<Vec<PathBuf>>,
     /// Urls to be probed
     #[structopt(short, long)]
     pub urls: Option<Vec<String>>,
+    /// File listing paths to be probed, one per line; `-` reads the list from stdin
+    #[structopt(long)]
+    pub path_file: Option<String>,
+    /// File listing urls to be probed, one per line; `-` reads the list from stdin
+    #[structopt(long)]
+    pub url_file: Option<String>,
+    /// Maximum number of documents probed at once [default: 8]
+    #[structopt(short, long)]
+    pub concurrency: Option<usize>,
+    /// Rules taken from the `[[extract]]` tables of the configuration
+    #[structopt(skip)]
+    pub extract: Vec<ExtractRule>,
 }
 
 #[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
@@ -39,18 +65,156 @@ pub enum HtmlOpts {
     /// Returns the specified headers
     #[structopt(name = "headers")]
     Headers(HtmlParseOpts),
+    /// Returns every image with its alt text, dimensions and format, and the problems
+    /// found in them
+    #[structopt(name = "images")]
+    Images(ImagesOpts),
     /// Returns the specified links
     #[structopt(name = "links")]
     Links(HtmlParseOpts),
+    /// Returns every heading in document order as a tree, and the problems found in
+    /// their hierarchy
+    #[structopt(name = "outline")]
+    Outline(HtmlParseOpts),
     /// Returns the title of the page
     #[structopt(name = "title")]
     PageTitle(HtmlParseOpts),
+    /// Returns the status, headers, redirects and timing of each url's response
+    #[structopt(name = "response")]
+    Response(HtmlParseOpts),
+    /// Returns the Open Graph and Twitter Card properties and the problems found in them
+    #[structopt(name = "social")]
+    Social(HtmlParseOpts),
+    /// Returns the JSON-LD, microdata and RDFa items and the problems found in them
+    #[structopt(name = "structured-data")]
+    StructuredData(HtmlParseOpts),
+    /// Returns the text, inner html or an attribute of elements matching a css selector
+    #[structopt(name = "select")]
+    Select(SelectOpts),
+    /// Checks the title, description and headers against the audit rules
+    #[structopt(name = "audit")]
+    Audit(AuditOpts),
+    /// Requests every link and reports those that are broken
+    #[structopt(name = "check-links")]
+    CheckLinks(CheckLinksOpts),
+    /// Returns whether the page can be indexed, from robots.txt, robots meta tags,
+    /// X-Robots-Tag headers and the canonical url
+    #[structopt(name = "indexability")]
+    Indexability(IndexabilityOpts),
     /// Error Value
     NotSelected,
 }
 
+#[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
+pub struct AuditOpts {
+    #[structopt(flatten)]
+    pub opts: HtmlParseOpts,
+    /// Thresholds taken from the `[audit]` section of the configuration
+    #[structopt(skip)]
+    pub rules: AuditConfig,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
+pub struct CheckLinksOpts {
+    #[structopt(flatten)]
+    pub opts: HtmlParseOpts,
+    /// Maximum number of requests sent to a single host at once [default: 2]
+    #[structopt(long)]
+    pub per_host: Option<usize>,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
+pub struct ImagesOpts {
+    #[structopt(flatten)]
+    pub opts: HtmlParseOpts,
+    /// Requests every image to record its size and content type
+    #[structopt(long)]
+    pub fetch: bool,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
+pub struct IndexabilityOpts {
+    #[structopt(flatten)]
+    pub opts: HtmlParseOpts,
+    /// Crawler the verdict is given for, such as `bingbot` [default: googlebot]
+    #[structopt(long)]
+    pub bot: Option<String>,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
+pub struct SelectOpts {
+    /// Css selector, such as `.price` or `nav.breadcrumbs a`
+    pub selector: String,
+    /// Returns the value of this attribute instead of the text
+    #[structopt(long, conflicts_with = "inner-html")]
+    pub attr: Option<String>,
+    /// Returns the inner html instead of the text
+    #[structopt(long)]
+    pub inner_html: bool,
+    #[structopt(flatten)]
+    pub opts: HtmlParseOpts,
+}
+
+/// Html query to be run against each probed document
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum HtmlQuery {
+    Audit(AuditConfig),
+    CheckLinks { per_host: usize },
+    Description,
+    Headers,
+    Images { fetch: bool },
+    Indexability { bot: String },
+    Links,
+    Outline,
+    PageTitle,
+    Response,
+    Select(Selection),
+    Social,
+    StructuredData,
+}
+
+/// Html query along with the documents it should be run against
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProbeRequest {
+    pub query: HtmlQuery,
+    pub opts: HtmlParseOpts,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
+pub struct CrawlOpts {
+    /// Url the crawl starts from; only pages on its host are visited
+    pub url: String,
+    /// Maximum number of links followed away from the start url [default: 3]
+    #[structopt(long)]
+    pub max_depth: Option<usize>,
+    /// Maximum number of pages visited [default: 100]
+    #[structopt(long)]
+    pub max_pages: Option<usize>,
+    /// Only follows urls matching one of these regular expressions
+    #[structopt(long)]
+    pub include: Option<Vec<String>>,
+    /// Never follows urls matching any of these regular expressions
+    #[structopt(long)]
+    pub exclude: Option<Vec<String>>,
+    /// Milliseconds to wait between requests [default: 250]
+    #[structopt(long)]
+    pub delay: Option<u64>,
+    /// Crawler, such as `googlebot`, whose robots.txt rules are obeyed and
+    /// whose indexability verdict is given for every page; robots.txt is not
+    /// read without it
+    #[structopt(long)]
+    pub bot: Option<String>,
+    /// Rules taken from the `[[extract]]` tables of the configuration
+    #[structopt(skip)]
+    #[serde(default)]
+    pub extract: Vec<ExtractRule>,
+}
+
 #[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
 pub enum CommandOpts {
+    /// Crawls a site breadth first starting from the given url
+    #[structopt(name = "crawl")]
+    Crawl(CrawlOpts),
     /// Probes specified Html document
     #[structopt(name = "probe")]
     Probe(HtmlOpts),
@@ -61,7 +225,23 @@ pub enum CommandOpts {
 // === Cli ===
 
 #[derive(StructOpt, Clone, Debug, Deserialize, Serialize)]
+#[structopt(about = env!("CARGO_PKG_DESCRIPTION"))]
 pub struct Cli {
+    /// Reads default options from the specified file instead of `inquest.toml`
+    #[structopt(long)]
+    config: Option<String>,
+    /// Output format [default: table]
+    #[structopt(short, long, possible_values = &Format::VARIANTS)]
+    format: Option<Format>,
+    /// Writes output to the specified file instead of stdout
+    #[structopt(short, long)]
+    output: Option<String>,
+    /// Lowest audit severity that fails the run [default: error]
+    #[structopt(long, possible_values = &Severity::VARIANTS)]
+    fail_on: Option<Severity>,
+    /// Http client options
+    #[structopt(flatten)]
+    http: HttpOpts,
     /// System Command Options
     #[structopt(subcommand)]
     cmd: Option<CommandOpts>,
@@ -71,9 +251,55 @@ pub struct Cli {
 
 impl Cli {
     /// Instantiates CLI and returns command line arguments
-    pub fn init() -> Cli {
+    ///
+    /// Invalid arguments, as well as help and version requests, are returned
+    /// as clap's error for the caller to print.
+    pub fn init() -> std::result::Result<Cli, clap::Error> {
+        let cli = Cli::from_iter_safe(std::env::args_os())?;
         event!(target: CLI, Level::DEBUG, "cli initialized");
-        Cli::from_args()
+        Ok(cli)
+    }
+
+    /// Reads the project configuration and fills the options it sets
+    pub fn load_config(&mut self) -> Result<(), Error> {
+        let config = Config::load(self.config.as_deref())?;
+        self.configure(&config);
+        Ok(())
+    }
+
+    /// Fills every option not given on the command line from `config`
+    pub fn configure(&mut self, config: &Config) {
+        self.format = self.format.or(config.output.format);
+        if self.output.is_none() {
+            self.output = config.output.path.clone();
+        }
+        self.fail_on = self.fail_on.or(Some(config.audit.fail_on));
+        self.http.merge(&config.http);
+        if let Some(CommandOpts::Probe(HtmlOpts::Audit(audit))) = &mut self.cmd {
+            audit.rules = config.audit.clone();
+        }
+        if let Some(opts) = self.cmd.as_mut().and_then(CommandOpts::parse_opts_mut) {
+            opts.merge(&config.probe);
+            opts.extract = config.extract.clone();
+        }
+        if let Some(CommandOpts::Crawl(crawl)) = &mut self.cmd {
+            crawl.extract = config.extract.clone();
+        }
+    }
+
+    /// Returns the selected output format
+    pub fn format(&self) -> Format {
+        self.format.unwrap_or(Format::Table)
+    }
+
+    /// Returns the lowest audit severity that fails the run
+    pub fn fail_on(&self) -> Severity {
+        self.fail_on.unwrap_or_default()
+    }
+
+    /// Returns the file output should be written to, if any
+    pub fn output(&self) -> Option<&str> {
+        self.output.as_deref()
     }
 
     /// Returns command selected by user via the cli
@@ -84,17 +310,37 @@ impl Cli {
         }
     }
 
-    pub fn service() -> BoxService<Request<Json>, Response<Json>, Error> {
-        let srv = ServiceBuilder::new()
-            .layer(CliLayer::new())
+    /// Builds the cli service; each report is sent to `reporter` as soon as it
+    /// is available, and the response only counts them
+    pub fn service(
+        &self,
+        reporter: Reporter<Report>,
+    ) -> Result<BoxService<Request<Json>, Response<Json>, Error>, Error> {
+        let client = self.http.client()?;
+        let probe = ServiceBuilder::new()
             .layer(CommandLayer::new())
             .layer(HtmlOptsLayer::new())
-            .service_fn(|req: Request<Json>| async move {
-                let json = req.into_body();
-                let res = Response::new(json);
-                Ok::<_, Error>(res)
-            });
-        BoxService::new(srv)
+            .service(ProbeService::new(client.clone(), reporter.clone()));
+        let crawl = ServiceBuilder::new()
+            .layer(CommandLayer::new())
+            .service(CrawlService::new(client, reporter));
+
+        let srv = ServiceBuilder::new()
+            .layer(CliLayer::new())
+            .service(Steer::new(
+                [BoxService::new(probe), BoxService::new(crawl)],
+                Cli::route,
+            ));
+        Ok(BoxService::new(srv))
+    }
+
+    /// Picks the service stack handling the selected command; the order
+    /// matches the stacks built in `service`
+    fn route<S>(req: &Request<Json>, _services: &[S]) -> usize {
+        match req.body().data::<Cli>().map(Cli::command) {
+            Ok(CommandOpts::Crawl(_)) => 1,
+            _ => 0,
+        }
     }
 }
 
@@ -116,8 +362,74 @@ impl IntoRequest<Bytes> for Cli {
     }
 }
 
+// === impl HtmlParseOpts ===
+
+impl HtmlParseOpts {
+    /// Fills any unset option from `defaults`
+    pub fn merge(&mut self, defaults: &HtmlParseOpts) {
+        if self.tags.is_none() {
+            self.tags = defaults.tags.clone();
+        }
+        if self.paths.is_none() {
+            self.paths = defaults.paths.clone();
+        }
+        if self.urls.is_none() {
+            self.urls = defaults.urls.clone();
+        }
+        if self.path_file.is_none() {
+            self.path_file = defaults.path_file.clone();
+        }
+        if self.url_file.is_none() {
+            self.url_file = defaults.url_file.clone();
+        }
+        if self.concurrency.is_none() {
+            self.concurrency = defaults.concurrency;
+        }
+    }
+}
+
+// === impl HtmlOpts ===
+
+impl HtmlOpts {
+    pub fn parse_opts_mut(&mut self) -> Option<&mut HtmlParseOpts> {
+        match self {
+            HtmlOpts::Description(opts)
+            | HtmlOpts::Headers(opts)
+            | HtmlOpts::Links(opts)
+            | HtmlOpts::Outline(opts)
+            | HtmlOpts::PageTitle(opts)
+            | HtmlOpts::Response(opts)
+            | HtmlOpts::Social(opts)
+            | HtmlOpts::StructuredData(opts) => Some(opts),
+            HtmlOpts::Audit(audit) => Some(&mut audit.opts),
+            HtmlOpts::Select(select) => Some(&mut select.opts),
+            HtmlOpts::CheckLinks(check) => Some(&mut check.opts),
+            HtmlOpts::Images(images) => Some(&mut images.opts),
+            HtmlOpts::Indexability(index) => Some(&mut index.opts),
+            HtmlOpts::NotSelected => None,
+        }
+    }
+}
+
+// === impl ProbeRequest ===
+
+impl ProbeRequest {
+    pub fn new(query: HtmlQuery, opts: HtmlParseOpts) -> ProbeRequest {
+        ProbeRequest { query, opts }
+    }
+}
+
 // === impl CommandOpts ===
 
+impl CommandOpts {
+    pub fn parse_opts_mut(&mut self) -> Option<&mut HtmlParseOpts> {
+        match self {
+            CommandOpts::Probe(opts) => opts.parse_opts_mut(),
+            CommandOpts::Crawl(_) | CommandOpts::NotSelected => None,
+        }
+    }
+}
+
 impl IntoResponse<Bytes> for CommandOpts {
     fn into_response(self) -> Response<Bytes> {
         let bytes = match serde_json::to_vec(&self) {
@@ -45,9 +45,9 @@ impl<S> Service<Request<Json>> for CliService<S>
     type Error = S::Error;
     type Future = CliServiceFuture<S::Future>;
 
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         event!(target: CLI, Level::TRACE, "polling cli service...");
-        Poll::Ready(Ok(()))
+        self.inner.poll_ready(cx)
     }
 
     fn call(&mut self, req: Request<Json>) -> Self::Future {
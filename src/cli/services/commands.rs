@@ -8,16 +8,22 @@ use std::task::{Context, Poll};
 use tower::{Layer, Service};
 use tracing::{event, Level};
 use crate::data::Json;
+use crate::error::{Error, ErrorKind, ErrorType};
 use crate::logging::CLI;
 
 pub(crate) struct CommandOptsService<S> {
     inner: S,
 }
 
-#[pin_project]
-pub(crate) struct CommandOptsFuture<F> {
-    #[pin]
-    future: F,
+#[pin_project(project = CommandOptsFutureProj)]
+pub(crate) enum CommandOptsFuture<F, E> {
+    Inner {
+        #[pin]
+        future: F,
+    },
+    Error {
+        error: Option<E>,
+    },
 }
 
 pub(crate) struct CommandLayer;
@@ -33,35 +39,44 @@ impl<S> CommandOptsService<S> {
 impl<S> Service<Request<Json>> for CommandOptsService<S>
 where
     S: Service<Request<Json>, Response = Response<Json>> + Send + 'static,
-    S::Error: Debug + Display,
+    S::Error: Debug + Display + From<Error>,
     S::Future: 'static + Send,
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = CommandOptsFuture<S::Future>;
+    type Future = CommandOptsFuture<S::Future, S::Error>;
 
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
     }
 
     fn call(&mut self, req: Request<Json>) -> Self::Future {
-        let cli: Cli = req.into_body().data();
-        let opts = cli.command();
-        match opts {
-            CommandOpts::Probe(opts) => {
-                let json = Json::new(opts);
-                CommandOptsFuture {
-                    future: self.inner.call(Request::new(json)),
-                }
-            }
-            _ => panic!("unimplemented")
+        let json = req
+            .into_body()
+            .data::<Cli>()
+            .and_then(|cli| match cli.command() {
+                CommandOpts::Crawl(opts) => Json::new(opts),
+                CommandOpts::Probe(opts) => Json::new(opts),
+                CommandOpts::NotSelected => Err(Error::new(ErrorType::SimpleMessage(
+                    ErrorKind::InvalidInput,
+                    &"no command selected",
+                ))),
+            });
+
+        match json {
+            Ok(json) => CommandOptsFuture::Inner {
+                future: self.inner.call(Request::new(json)),
+            },
+            Err(err) => CommandOptsFuture::Error {
+                error: Some(S::Error::from(err)),
+            },
         }
     }
 }
 
 // === Future ===
 
-impl<F, E> Future for CommandOptsFuture<F>
+impl<F, E> Future for CommandOptsFuture<F, E>
 where
     F: Future<Output = Result<Response<Json>, E>>,
     E: Debug + Display,
@@ -69,16 +84,19 @@ where
     type Output = Result<Response<Json>, E>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
-
         event!(
             target: CLI,
             Level::TRACE,
             "polling cli command service(s)..."
         );
-        let res: F::Output = match this.future.poll(cx) {
-            Poll::Ready(res) => res,
-            Poll::Pending => return Poll::Pending,
+        let res: F::Output = match self.project() {
+            CommandOptsFutureProj::Inner { future } => match future.poll(cx) {
+                Poll::Ready(res) => res,
+                Poll::Pending => return Poll::Pending,
+            },
+            CommandOptsFutureProj::Error { error } => {
+                Err(error.take().expect("polled after completion"))
+            }
         };
 
         match &res {
@@ -105,7 +123,7 @@ impl CommandLayer {
 impl<S> Layer<S> for CommandLayer
 where
     S: Service<Request<Json>, Response = Response<Json>> + Send + 'static,
-    S::Error: Debug + Display,
+    S::Error: Debug + Display + From<Error>,
     S::Future: 'static + Send,
 {
     type Service = CommandOptsService<S>;
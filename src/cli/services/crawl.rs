@@ -0,0 +1,87 @@
+use crate::cli::CrawlOpts;
+use crate::crawl::Crawler;
+use crate::data::Json;
+use crate::error::Error;
+use crate::http::Client;
+use crate::logging::CLI;
+use crate::report::Report;
+use crate::service::{IntoResponse, Request, Response};
+use crate::utils::Reporter;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::Service;
+use tracing::{event, Level};
+
+/// Terminal service; crawls the requested site reporting each page as it is visited
+pub(crate) struct CrawlService {
+    client: Client,
+    reporter: Reporter<Report>,
+}
+
+// === Service ===
+
+impl CrawlService {
+    pub fn new(client: Client, reporter: Reporter<Report>) -> Self {
+        Self { client, reporter }
+    }
+
+    fn crawler(&self, opts: &CrawlOpts) -> Result<Crawler, Error> {
+        let mut crawler = Crawler::new(self.client.clone());
+        if let Some(depth) = opts.max_depth {
+            crawler = crawler.max_depth(depth);
+        }
+        if let Some(pages) = opts.max_pages {
+            crawler = crawler.max_pages(pages);
+        }
+        if let Some(delay) = opts.delay {
+            crawler = crawler.delay(Duration::from_millis(delay));
+        }
+        if let Some(patterns) = &opts.include {
+            crawler = crawler.include(patterns)?;
+        }
+        if let Some(patterns) = &opts.exclude {
+            crawler = crawler.exclude(patterns)?;
+        }
+        if let Some(bot) = &opts.bot {
+            crawler = crawler.bot(bot);
+        }
+        if !opts.extract.is_empty() {
+            crawler = crawler.extract(&opts.extract)?;
+        }
+        Ok(crawler)
+    }
+}
+
+impl Service<Request<Json>> for CrawlService {
+    type Response = Response<Json>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Json>) -> Self::Future {
+        let crawl = req
+            .into_body()
+            .data::<CrawlOpts>()
+            .and_then(|opts| Ok((self.crawler(&opts)?, opts)));
+        let reporter = self.reporter.clone();
+        Box::pin(async move {
+            let (crawler, opts) = crawl?;
+            let mut pages = 0;
+            crawler
+                .run(&opts.url, |page| {
+                    // see ProbeService
+                    let _ = reporter.send(Report::Page(page));
+                    pages += 1;
+                })
+                .await?;
+
+            event!(target: CLI, Level::TRACE, "crawl complete; pages={}", pages);
+            Ok(Json::new(pages)?.into_response())
+        })
+    }
+}
@@ -1,8 +1,11 @@
 use std::fmt::{Debug, Display};
 use std::future::Future;
 use std::pin::Pin;
-use crate::cli::{CommandOpts, HtmlOpts};
-use crate::error::Error;
+use crate::check::DEFAULT_PER_HOST;
+use crate::cli::{CommandOpts, HtmlOpts, HtmlQuery, ProbeRequest};
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::html::{Extract, Selection};
+use crate::index::DEFAULT_BOT;
 use crate::service::{IntoRequest, Request, Response};
 use futures::future;
 use pin_project::pin_project;
@@ -16,10 +19,15 @@ pub(crate) struct HtmlOptsService<S> {
     inner: S
 }
 
-#[pin_project]
-pub(crate) struct HtmlOptsServiceFuture<F> {
-    #[pin]
-    future: F,
+#[pin_project(project = HtmlOptsServiceFutureProj)]
+pub(crate) enum HtmlOptsServiceFuture<F, E> {
+    Inner {
+        #[pin]
+        future: F,
+    },
+    Error {
+        error: Option<E>,
+    },
 }
 
 pub(crate) struct HtmlOptsLayer;
@@ -35,32 +43,77 @@ impl<S> HtmlOptsService<S> {
 impl<S> Service<Request<Json>> for HtmlOptsService<S>
 where
     S: Service<Request<Json>, Response = Response<Json>> + Send + 'static,
-    S::Error: Debug + Display,
+    S::Error: Debug + Display + From<Error>,
     S::Future: Send + 'static
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = HtmlOptsServiceFuture<S::Future>;
+    type Future = HtmlOptsServiceFuture<S::Future, S::Error>;
 
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
     }
 
     fn call(&mut self, req: Request<Json>) -> Self::Future {
-        let opts: HtmlOpts = req.into_body().data();
-        match opts {
-            HtmlOpts::Links(opts) => {
-                let json = Json::new(opts);
-                HtmlOptsServiceFuture {
-                    future: self.inner.call(json.into_request())
+        let json = req
+            .into_body()
+            .data::<HtmlOpts>()
+            .and_then(|opts| match opts {
+                HtmlOpts::Description(opts) => Ok(ProbeRequest::new(HtmlQuery::Description, opts)),
+                HtmlOpts::Headers(opts) => Ok(ProbeRequest::new(HtmlQuery::Headers, opts)),
+                HtmlOpts::Links(opts) => Ok(ProbeRequest::new(HtmlQuery::Links, opts)),
+                HtmlOpts::Outline(opts) => Ok(ProbeRequest::new(HtmlQuery::Outline, opts)),
+                HtmlOpts::PageTitle(opts) => Ok(ProbeRequest::new(HtmlQuery::PageTitle, opts)),
+                HtmlOpts::Response(opts) => Ok(ProbeRequest::new(HtmlQuery::Response, opts)),
+                HtmlOpts::Social(opts) => Ok(ProbeRequest::new(HtmlQuery::Social, opts)),
+                HtmlOpts::StructuredData(opts) => {
+                    Ok(ProbeRequest::new(HtmlQuery::StructuredData, opts))
                 }
-            }
-            _ => panic!("unimplemented")
+                HtmlOpts::Audit(audit) => Ok(ProbeRequest::new(HtmlQuery::Audit(audit.rules), audit.opts)),
+                HtmlOpts::Select(select) => {
+                    let extract = match (select.attr, select.inner_html) {
+                        (Some(name), _) => Extract::Attr(name),
+                        (None, true) => Extract::InnerHtml,
+                        (None, false) => Extract::Text,
+                    };
+                    let selection = Selection::new(&select.selector, extract)?;
+                    Ok(ProbeRequest::new(HtmlQuery::Select(selection), select.opts))
+                }
+                HtmlOpts::CheckLinks(check) => Ok(ProbeRequest::new(
+                    HtmlQuery::CheckLinks {
+                        per_host: check.per_host.unwrap_or(DEFAULT_PER_HOST),
+                    },
+                    check.opts,
+                )),
+                HtmlOpts::Images(images) => Ok(ProbeRequest::new(
+                    HtmlQuery::Images { fetch: images.fetch },
+                    images.opts,
+                )),
+                HtmlOpts::Indexability(index) => Ok(ProbeRequest::new(
+                    HtmlQuery::Indexability {
+                        bot: index.bot.unwrap_or_else(|| DEFAULT_BOT.to_string()),
+                    },
+                    index.opts,
+                )),
+                HtmlOpts::NotSelected => Err(Error::new(ErrorType::SimpleMessage(
+                    ErrorKind::InvalidInput,
+                    &"no html option selected",
+                ))),
+            })
+            .and_then(Json::new);
+
+        match json {
+            Ok(json) => HtmlOptsServiceFuture::Inner {
+                future: self.inner.call(json.into_request()),
+            },
+            Err(err) => HtmlOptsServiceFuture::Error {
+                error: Some(S::Error::from(err)),
+            },
         }
     }
 }
 
-impl<F, E> Future for HtmlOptsServiceFuture<F>
+impl<F, E> Future for HtmlOptsServiceFuture<F, E>
 where
     F: Future<Output = Result<Response<Json>, E>>,
     E: Debug + Display
@@ -68,12 +121,15 @@ where
     type Output = Result<Response<Json>, E>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
-
         event!(target: CLI, Level::TRACE, "polling html inner service(s)...");
-        let res: F::Output = match this.future.poll(cx) {
-            Poll::Ready(res) => res,
-            Poll::Pending => return Poll::Pending,
+        let res: F::Output = match self.project() {
+            HtmlOptsServiceFutureProj::Inner { future } => match future.poll(cx) {
+                Poll::Ready(res) => res,
+                Poll::Pending => return Poll::Pending,
+            },
+            HtmlOptsServiceFutureProj::Error { error } => {
+                Err(error.take().expect("polled after completion"))
+            }
         };
 
         match &res {
@@ -1,3 +1,5 @@
 pub mod cli;
 pub mod commands;
-pub mod html;
\ No newline at end of file
+pub mod crawl;
+pub mod html;
+pub mod probe;
@@ -0,0 +1,303 @@
+use crate::check::LinkChecker;
+use crate::cli::{HtmlParseOpts, HtmlQuery, ProbeRequest};
+use crate::data::{Json, Origin};
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::extract::{Extracted, Extractor};
+use crate::file::{File, STDIN};
+use crate::html::HtmlTag;
+use crate::http::Client;
+use crate::index::RobotsCache;
+use crate::logging::CLI;
+use crate::parse::{Parse, Utils};
+use crate::probe::Probe;
+use crate::report::{ProbeData, ProbeReport, Report};
+use crate::service::{IntoResponse, Request, Response};
+use crate::utils::Reporter;
+use futures::{stream, StreamExt};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Service, ServiceBuilder, ServiceExt};
+use tracing::{event, Level};
+
+/// Number of targets probed at once when not specified
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Terminal service; probes every requested document and runs the selected query
+pub(crate) struct ProbeService {
+    client: Client,
+    reporter: Reporter<Report>,
+}
+
+/// Document to be probed
+pub(crate) struct Target {
+    origin: Origin,
+    location: String,
+}
+
+/// Probes a single target; failures are captured in the returned report
+#[derive(Clone)]
+pub(crate) struct TargetService {
+    client: Client,
+    query: Arc<HtmlQuery>,
+    tags: Arc<Vec<HtmlTag>>,
+    extractor: Arc<Extractor>,
+    checker: Option<LinkChecker>,
+    robots: Option<RobotsCache>,
+}
+
+// === Service ===
+
+impl ProbeService {
+    pub fn new(client: Client, reporter: Reporter<Report>) -> Self {
+        Self { client, reporter }
+    }
+}
+
+impl Service<Request<Json>> for ProbeService {
+    type Response = Response<Json>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Json>) -> Self::Future {
+        let request = req.into_body().data::<ProbeRequest>();
+        let client = self.client.clone();
+        let reporter = self.reporter.clone();
+        Box::pin(async move {
+            let ProbeRequest { query, opts } = request?;
+            let tags = opts
+                .tags
+                .iter()
+                .flatten()
+                .map(|tag| tag.parse())
+                .collect::<Result<Vec<HtmlTag>, Error>>()?;
+            if let (HtmlQuery::Headers, Some(tag)) =
+                (&query, tags.iter().find(|tag| !tag.is_header()))
+            {
+                return Err(Error::new(ErrorType::Message(
+                    ErrorKind::InvalidHtmlTag,
+                    format!("{} is not a header", tag),
+                )));
+            }
+
+            let extractor = Extractor::new(&opts.extract)?;
+            let targets = Target::collect(&opts).await?;
+            let local = targets.iter().find(|t| !matches!(t.origin, Origin::Http));
+            if let (HtmlQuery::Response, Some(target)) = (&query, local) {
+                return Err(Error::new(ErrorType::Message(
+                    ErrorKind::InvalidInput,
+                    format!("{} is not a url; only urls have a response", target.location),
+                )));
+            }
+            // credentials go to the hosts asked for, never to the sites they link to
+            for target in targets.iter().filter(|t| matches!(t.origin, Origin::Http)) {
+                client.authorize(&target.location);
+            }
+
+            let concurrency = opts.concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+            event!(
+                target: CLI,
+                Level::DEBUG,
+                "probing targets; count={} concurrency={}",
+                targets.len(),
+                concurrency
+            );
+
+            let srv = ServiceBuilder::new()
+                .concurrency_limit(concurrency)
+                .service(TargetService::new(client, query, tags, extractor));
+            let mut responses = srv.call_all(stream::iter(targets)).unordered();
+
+            let mut documents = 0;
+            while let Some(report) = responses.next().await {
+                let report = report.map_err(|err| Error::new(ErrorType::Message(
+                    ErrorKind::Failed,
+                    err.to_string(),
+                )))?;
+                // a closed receiver means its owner stopped writing reports and
+                // reports why itself
+                let _ = reporter.send(Report::Probe(report));
+                documents += 1;
+            }
+
+            event!(target: CLI, Level::TRACE, "probe complete; documents={}", documents);
+            Ok(Json::new(documents)?.into_response())
+        })
+    }
+}
+
+// === impl Target ===
+
+impl Target {
+    pub fn new(origin: Origin, location: String) -> Target {
+        Target { origin, location }
+    }
+
+    /// Gathers every target listed directly or through a list file
+    async fn collect(opts: &HtmlParseOpts) -> Result<Vec<Target>, Error> {
+        let stdin_error = || {
+            Error::new(ErrorType::SimpleMessage(
+                ErrorKind::InvalidInput,
+                &"stdin can only be read once",
+            ))
+        };
+        let lists = [&opts.path_file, &opts.url_file]
+            .iter()
+            .filter(|f| f.as_deref() == Some(STDIN))
+            .count();
+        if lists > 1 {
+            return Err(stdin_error());
+        }
+
+        let mut paths = opts
+            .paths
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .map(Parse::<Utils>::path_to_string)
+            .collect::<Vec<_>>();
+        if let Some(file) = &opts.path_file {
+            paths.extend(File::lines(file).await?);
+        }
+
+        let mut urls = opts.urls.clone().unwrap_or_default();
+        if let Some(file) = &opts.url_file {
+            urls.extend(File::lines(file).await?);
+        }
+
+        if lists + paths.iter().filter(|p| *p == STDIN).count() > 1 {
+            return Err(stdin_error());
+        }
+
+        Ok(paths
+            .into_iter()
+            .map(|path| match path.as_str() {
+                STDIN => Target::new(Origin::Stdin, path),
+                _ => Target::new(Origin::FileSystem, path),
+            })
+            .chain(urls.into_iter().map(|url| Target::new(Origin::Http, url)))
+            .collect())
+    }
+}
+
+// === impl TargetService ===
+
+impl TargetService {
+    pub fn new(client: Client, query: HtmlQuery, tags: Vec<HtmlTag>, extractor: Extractor) -> Self {
+        // shared by every target so each link is only requested once
+        let checker = match query {
+            HtmlQuery::CheckLinks { per_host } => Some(LinkChecker::new(client.clone(), per_host)),
+            _ => None,
+        };
+        let robots = match query {
+            HtmlQuery::Indexability { .. } => Some(RobotsCache::new(client.clone())),
+            _ => None,
+        };
+        Self {
+            client,
+            query: Arc::new(query),
+            tags: Arc::new(tags),
+            extractor: Arc::new(extractor),
+            checker,
+            robots,
+        }
+    }
+
+    async fn document(
+        path: &str,
+        query: &HtmlQuery,
+        tags: &[HtmlTag],
+        extractor: &Extractor,
+    ) -> Result<(ProbeData, Vec<Extracted>), Error> {
+        event!(target: CLI, Level::DEBUG, "probing document; path={}", path);
+        let probe = Probe::new().document().html().from(path).await?;
+        Ok((ProbeData::collect(&probe, query, tags)?, extractor.run(&probe)?))
+    }
+
+    async fn stdin(
+        query: &HtmlQuery,
+        tags: &[HtmlTag],
+        extractor: &Extractor,
+    ) -> Result<(ProbeData, Vec<Extracted>), Error> {
+        event!(target: CLI, Level::DEBUG, "probing document from stdin");
+        let probe = Probe::new().document().html().read(tokio::io::stdin()).await?;
+        Ok((ProbeData::collect(&probe, query, tags)?, extractor.run(&probe)?))
+    }
+
+    async fn http(
+        client: Client,
+        url: &str,
+        query: &HtmlQuery,
+        tags: &[HtmlTag],
+        extractor: &Extractor,
+        robots: Option<RobotsCache>,
+    ) -> Result<(ProbeData, Vec<Extracted>), Error> {
+        event!(target: CLI, Level::DEBUG, "probing url; url={}", url);
+        // fetched first; the parsed document cannot be held across an await
+        let blocked = match (query, robots) {
+            (HtmlQuery::Indexability { bot }, Some(robots)) => robots.disallowed_by(bot, url).await,
+            _ => None,
+        };
+        let probe = Probe::new().http().html().client(client).from(url).await?;
+        let data = ProbeData::response(&probe, query, tags, blocked.as_deref())?;
+        Ok((data, extractor.run(&probe)?))
+    }
+}
+
+impl Service<Target> for TargetService {
+    type Response = ProbeReport;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, target: Target) -> Self::Future {
+        let client = self.client.clone();
+        let query = self.query.clone();
+        let tags = self.tags.clone();
+        let extractor = self.extractor.clone();
+        let checker = self.checker.clone();
+        let robots = self.robots.clone();
+        Box::pin(async move {
+            let Target { origin, location } = target;
+            let data = match origin {
+                Origin::Http => {
+                    let client = client.clone();
+                    TargetService::http(client, &location, &query, &tags, &extractor, robots).await
+                }
+                Origin::Stdin => TargetService::stdin(&query, &tags, &extractor).await,
+                _ => TargetService::document(&location, &query, &tags, &extractor).await,
+            };
+            let data = match (data, checker) {
+                (Ok((ProbeData::Links(links), extracted)), Some(checker)) => {
+                    Ok((ProbeData::LinkChecks(checker.check_all(&links).await), extracted))
+                }
+                (Ok((ProbeData::Images(mut images), extracted)), _)
+                    if matches!(*query, HtmlQuery::Images { fetch: true }) =>
+                {
+                    images.fetch(&client).await;
+                    Ok((ProbeData::Images(images), extracted))
+                }
+                (data, _) => data,
+            };
+
+            match data {
+                Ok((data, extracted)) => {
+                    Ok(ProbeReport::new(origin, &location, data).with_extracted(extracted))
+                }
+                Err(err) => {
+                    event!(target: CLI, Level::WARN, "probe failed; source={} {}", location, err);
+                    let report = ProbeReport::failed(origin, &location, &err);
+                    Ok(report.with_extracted(extractor.blank()))
+                }
+            }
+        })
+    }
+}
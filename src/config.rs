@@ -0,0 +1,122 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audit::Severity;
+use crate::cli::HtmlParseOpts;
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::extract::ExtractRule;
+use crate::http::HttpOpts;
+use crate::output::Format;
+use crate::utils::Result;
+
+/// Configuration file picked up from the working directory
+pub const CONFIG_FILE: &str = "inquest.toml";
+
+/// Project wide defaults; any option given on the command line takes precedence
+///
+/// ```toml
+/// [probe]
+/// tags = ["1", "2"]
+/// concurrency = 16
+///
+/// [http]
+/// user_agent = "googlebot-smartphone"
+/// headers = ["Accept-Language: en"]
+/// basic_auth = "staging:secret"
+/// timeout = 10
+///
+/// [output]
+/// format = "csv"
+///
+/// [audit]
+/// fail_on = "warning"
+/// title_max_length = 65
+///
+/// [[extract]]
+/// name = "author"
+/// selector = ".byline a"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub probe: HtmlParseOpts,
+    pub http: HttpOpts,
+    pub output: OutputConfig,
+    pub audit: AuditConfig,
+    /// Values pulled out of every probed or crawled page, reported as columns
+    pub extract: Vec<ExtractRule>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub format: Option<Format>,
+    pub path: Option<String>,
+}
+
+/// Thresholds the audit checks documents against
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// Lowest severity that fails the run
+    pub fail_on: Severity,
+    pub title_min_length: usize,
+    pub title_max_length: usize,
+    pub description_min_length: usize,
+    pub description_max_length: usize,
+    pub max_h1: usize,
+}
+
+// === impl Config ===
+
+impl Config {
+    /// Reads the configuration at `path`, or `inquest.toml` in the working
+    /// directory when no path is given
+    ///
+    /// A missing `inquest.toml` yields the defaults; a missing file that was
+    /// asked for explicitly is an error.
+    pub fn load(path: Option<&str>) -> Result<Config> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(CONFIG_FILE).is_file() => CONFIG_FILE,
+            None => return Ok(Config::default()),
+        };
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            Error::new(ErrorType::Message(ErrorKind::InvalidConfig, format!("{}; {}", path, err)))
+        })?;
+        contents.parse::<Config>().map_err(|err| {
+            Error::new(ErrorType::Message(ErrorKind::InvalidConfig, format!("{}; {}", path, err)))
+        })
+    }
+}
+
+impl FromStr for Config {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let config: Config = toml::from_str(s)?;
+        // the rules of `[[extract]]` are the only ones; see `Cli::configure`
+        if !config.probe.extract.is_empty() {
+            let message = "extract rules belong in [[extract]] tables, not in [probe]";
+            return Err(serde::de::Error::custom(message));
+        }
+        Ok(config)
+    }
+}
+
+// === impl AuditConfig ===
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            fail_on: Severity::default(),
+            title_min_length: 30,
+            title_max_length: 60,
+            description_min_length: 70,
+            description_max_length: 160,
+            max_h1: 1,
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use regex::Regex;
+use reqwest::Url;
+use tracing::{event, Level};
+
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::extract::{ExtractRule, Extractor};
+use crate::http::Client;
+use crate::index::{Indexability, RobotsCache};
+use crate::logging::CLI;
+use crate::probe::Probe;
+use crate::report::PageReport;
+use crate::utils::Result;
+
+/// Links followed away from the start url when not specified
+pub const DEFAULT_MAX_DEPTH: usize = 3;
+
+/// Pages visited when not specified
+pub const DEFAULT_MAX_PAGES: usize = 100;
+
+/// Milliseconds waited between requests when not specified
+pub const DEFAULT_DELAY: u64 = 250;
+
+/// Breadth first crawler over the pages of a single host
+pub struct Crawler {
+    client: Client,
+    max_depth: usize,
+    max_pages: usize,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    delay: Duration,
+    extractor: Extractor,
+    robots: RobotsCache,
+    bot: Option<String>,
+}
+
+// === impl Crawler ===
+
+impl Crawler {
+    pub fn new(client: Client) -> Crawler {
+        Crawler {
+            robots: RobotsCache::new(client.clone()),
+            client,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_pages: DEFAULT_MAX_PAGES,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            delay: Duration::from_millis(DEFAULT_DELAY),
+            extractor: Extractor::default(),
+            bot: None,
+        }
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn max_pages(mut self, pages: usize) -> Self {
+        self.max_pages = pages;
+        self
+    }
+
+    /// Only follows urls matching at least one of the patterns
+    pub fn include(mut self, patterns: &[String]) -> Result<Self> {
+        self.include = Crawler::patterns(patterns)?;
+        Ok(self)
+    }
+
+    /// Never follows urls matching any of the patterns
+    pub fn exclude(mut self, patterns: &[String]) -> Result<Self> {
+        self.exclude = Crawler::patterns(patterns)?;
+        Ok(self)
+    }
+
+    /// Time waited between two requests
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Obeys the robots.txt rules for `bot`, reporting the pages they disallow
+    /// as blocked without fetching them, and gives the indexability verdict
+    /// for it on every page
+    pub fn bot(mut self, bot: &str) -> Self {
+        self.bot = Some(bot.to_string());
+        self
+    }
+
+    /// Runs the extraction rules on every page visited
+    pub fn extract(mut self, rules: &[ExtractRule]) -> Result<Self> {
+        self.extractor = Extractor::new(rules)?;
+        Ok(self)
+    }
+
+    /// Crawls every reachable page on the start url's host, handing each
+    /// page to `visit` as soon as it has been probed
+    ///
+    /// Returns the number of pages visited.
+    pub async fn run<F>(&self, start: &str, mut visit: F) -> Result<usize>
+    where
+        F: FnMut(PageReport) + Send,
+    {
+        let mut start = Url::parse(start).map_err(|err| {
+            Error::new(ErrorType::Message(ErrorKind::InvalidUrl, format!("{}; {}", start, err)))
+        })?;
+        start.set_fragment(None);
+        let host = start.host_str().map(str::to_string);
+        self.client.authorize(start.as_str());
+
+        let mut seen = HashSet::from([start.to_string()]);
+        let mut queue = VecDeque::from([(start, 0)]);
+        let mut visited = 0;
+
+        while let Some((url, depth)) = queue.pop_front() {
+            if visited >= self.max_pages {
+                break;
+            }
+            if let Some(bot) = &self.bot {
+                if let Some(rule) = self.robots.disallowed_by(bot, url.as_str()).await {
+                    event!(target: CLI, Level::DEBUG, "page disallowed; url={} rule={}", url, rule);
+                    visited += 1;
+                    visit(
+                        PageReport::blocked(&url, depth)
+                            .with_indexability(Indexability::blocked(&rule))
+                            .with_extracted(self.extractor.blank()),
+                    );
+                    continue;
+                }
+            }
+            if visited > 0 && !self.delay.is_zero() {
+                tokio::time::sleep(self.delay).await;
+            }
+
+            event!(target: CLI, Level::DEBUG, "crawling page; url={} depth={}", url, depth);
+            let page = match self.page(&url, depth).await {
+                Ok(page) => page,
+                Err(err) => {
+                    event!(target: CLI, Level::WARN, "crawl failed; url={} {}", url, err);
+                    PageReport::failed(&url, depth, &err).with_extracted(self.extractor.blank())
+                }
+            };
+            visited += 1;
+
+            if depth < self.max_depth {
+                for link in page.links() {
+                    let link = match Url::parse(link) {
+                        Ok(link) => link,
+                        Err(_) => continue,
+                    };
+                    if link.host_str() == host.as_deref()
+                        && self.allowed(link.as_str())
+                        && seen.insert(link.to_string())
+                    {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+            visit(page);
+        }
+
+        event!(target: CLI, Level::TRACE, "crawl complete; pages={}", visited);
+        Ok(visited)
+    }
+
+    async fn page(&self, url: &Url, depth: usize) -> Result<PageReport> {
+        let probe = Probe::new()
+            .http()
+            .html()
+            .client(self.client.clone())
+            .from(url.as_str())
+            .await?;
+        let mut page = PageReport::collect(&probe, url, depth, probe.status())?;
+        // pages robots.txt disallows are never requested, so none is blocked
+        if let Some(bot) = &self.bot {
+            let index = Indexability::assess(&probe, probe.response(), None, bot)?;
+            page = page.with_indexability(index);
+        }
+        Ok(page.with_extracted(self.extractor.run(&probe)?))
+    }
+
+    fn allowed(&self, url: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|re| re.is_match(url)))
+            && !self.exclude.iter().any(|re| re.is_match(url))
+    }
+
+    fn patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|err| {
+                    Error::new(ErrorType::Message(ErrorKind::InvalidParameters, err.to_string()))
+                })
+            })
+            .collect()
+    }
+}
@@ -1,5 +1,5 @@
 #![allow(unused)]
-use crate::error::{Error, ErrorKind};
+use crate::error::{Error, ErrorKind, ErrorType};
 use crate::logging::{JSON, REQUEST};
 use crate::parse::Parse;
 use crate::service::{IntoRequest, IntoResponse, Request, Response};
@@ -65,11 +65,14 @@ pub enum DataType {
 /// @ = http
 ///
 /// & = internal
-#[derive(Serialize, Deserialize)]
+///
+/// \- = stdin
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Origin {
     FileSystem,
     Http,
     Internal,
+    Stdin,
 }
 
 /// Base `Data Structure` for application.
@@ -114,7 +117,8 @@ impl ByteController for Data {
         if !src.has_remaining() {
             return Err(Error::from(ErrorKind::Unknown));
         }
-        Ok(src.advance(n))
+        src.advance(n);
+        Ok(())
     }
 
     fn read_newline_decimal(src: &mut Cursor<&[u8]>) -> Result<u64> {
@@ -160,10 +164,10 @@ impl DataController for Data {
 // === impl JSON ===
 
 impl Json {
-    pub fn new<El: Serialize>(elem: El) -> Json {
-        Json {
-            data: Json::serialize(elem),
-        }
+    pub fn new<El: Serialize>(elem: El) -> Result<Json> {
+        Ok(Json {
+            data: Json::serialize(elem)?,
+        })
     }
 
     pub fn bytes(&self) -> Bytes {
@@ -174,27 +178,34 @@ impl Json {
         self.data
     }
 
-    pub fn data<'de, Res: Deserialize<'de>>(&'de self) -> Res {
+    pub fn data<'de, Res: Deserialize<'de>>(&'de self) -> Result<Res> {
         Json::deserialize(&self.data)
     }
 
-    pub fn set<El: Serialize>(&mut self, elem: El) {
-        self.data = Json::serialize(elem);
+    pub fn set<El: Serialize>(&mut self, elem: El) -> Result<()> {
+        self.data = Json::serialize(elem)?;
+        Ok(())
     }
 
-    pub fn deserialize<'de, Res: Deserialize<'de>>(bytes: &'de Bytes) -> Res {
-        serde_json::from_slice(bytes.chunk()).unwrap_or_else(|err| {
-            event!(target: JSON, Level::ERROR, "failed to deserialize bytes");
-            panic!("json deserialization failed; {}", err);
+    pub fn deserialize<'de, Res: Deserialize<'de>>(bytes: &'de Bytes) -> Result<Res> {
+        serde_json::from_slice(bytes.chunk()).map_err(|err| {
+            event!(target: JSON, Level::ERROR, "failed to deserialize bytes; {}", err);
+            Error::new(ErrorType::Message(
+                ErrorKind::Parse,
+                format!("json deserialization failed; {}", err),
+            ))
         })
     }
 
-    pub fn serialize<T: Serialize>(elem: T) -> Bytes {
+    pub fn serialize<T: Serialize>(elem: T) -> Result<Bytes> {
         match serde_json::to_vec(&elem) {
-            Ok(vec) => Bytes::from(vec),
+            Ok(vec) => Ok(Bytes::from(vec)),
             Err(err) => {
-                event!(target: JSON, Level::ERROR, "failed to serialize element");
-                panic!("json serialization failed; {}", err);
+                event!(target: JSON, Level::ERROR, "failed to serialize element; {}", err);
+                Err(Error::new(ErrorType::Message(
+                    ErrorKind::Parse,
+                    format!("json serialization failed; {}", err),
+                )))
             }
         }
     }
@@ -202,6 +213,7 @@ impl Json {
 
 // === impl std ===
 
+#[allow(clippy::derivable_impls)]
 impl Default for Json {
     fn default() -> Self {
         Json {
@@ -255,6 +267,7 @@ impl Display for Origin {
             Origin::FileSystem => write!(f, "file"),
             Origin::Http => write!(f, "http"),
             Origin::Internal => write!(f, "internal"),
+            Origin::Stdin => write!(f, "stdin"),
         }
     }
 }
@@ -279,26 +292,29 @@ impl From<Vec<Data>> for DataChunk {
     }
 }
 
-impl From<Bytes> for Json {
-    fn from(bytes: Bytes) -> Self {
-        Self {
-            data: Json::serialize(bytes),
-        }
+impl TryFrom<Bytes> for Json {
+    type Error = Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self> {
+        Ok(Self {
+            data: Json::serialize(bytes)?,
+        })
     }
 }
 
-impl From<Vec<u8>> for Json {
-    fn from(vec: Vec<u8>) -> Self {
-        let b = Bytes::from(vec);
-        Self {
-            data: Json::serialize(b),
-        }
+impl TryFrom<Vec<u8>> for Json {
+    type Error = Error;
+
+    fn try_from(vec: Vec<u8>) -> Result<Self> {
+        Json::try_from(Bytes::from(vec))
     }
 }
 
-impl From<&[u8]> for Json {
-    fn from(bits: &[u8]) -> Self {
-        Json::from(bits.to_vec())
+impl TryFrom<&[u8]> for Json {
+    type Error = Error;
+
+    fn try_from(bits: &[u8]) -> Result<Self> {
+        Json::try_from(bits.to_vec())
     }
 }
 
@@ -310,6 +326,7 @@ impl FromStr for Origin {
             "%" => Ok(Origin::FileSystem),
             "@" => Ok(Origin::Http),
             "&" => Ok(Origin::Internal),
+            "-" => Ok(Origin::Stdin),
             _ => Err(Error::from(ErrorKind::Parse)),
         }
     }
@@ -353,6 +370,7 @@ impl PartialEq<&str> for Origin {
             Origin::FileSystem => "path".eq(*other),
             Origin::Http => "http".eq(*other),
             Origin::Internal => "internal".eq(*other),
+            Origin::Stdin => "stdin".eq(*other),
         }
     }
 }
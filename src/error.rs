@@ -3,7 +3,7 @@
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ErrorKind {
     Create,
     Delete,
@@ -13,6 +13,7 @@ pub enum ErrorKind {
     FileNotFound,
     Html,
     Http,
+    InvalidConfig,
     InvalidData,
     InvalidHtmlTag,
     InvalidInput,
@@ -21,6 +22,7 @@ pub enum ErrorKind {
     InvalidSearch,
     InvalidUrl,
     InvalidUtf8,
+    Io,
     NotFound,
     Parse,
     Unimplemented,
@@ -40,6 +42,7 @@ impl ErrorKind {
             FileNotFound => "file not found",
             Html => "html error",
             Http => "Http error",
+            InvalidConfig => "invalid configuration",
             InvalidData => "invalid data",
             InvalidHtmlTag => "invalid HTML tag",
             InvalidInput => "invalid input",
@@ -48,6 +51,7 @@ impl ErrorKind {
             InvalidSearch => "invalid search parameter",
             InvalidUrl => "invalid url",
             InvalidUtf8 => "invalid UTF-8",
+            Io => "io error",
             NotFound => "not found",
             Parse => "parsing error",
             Unimplemented => "unimplemented",
@@ -60,13 +64,15 @@ impl ErrorKind {
 pub enum ErrorType {
     Simple(ErrorKind),
     SimpleMessage(ErrorKind, &'static &'static str),
+    Message(ErrorKind, String),
 }
 
 impl Display for ErrorType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             ErrorType::Simple(err) => write!(f, "{}", err.to_str()),
-            ErrorType::SimpleMessage(err, &msg) => write!(f, "{}: {}", err.to_str(), &msg)
+            ErrorType::SimpleMessage(err, &msg) => write!(f, "{}: {}", err.to_str(), &msg),
+            ErrorType::Message(err, msg) => write!(f, "{}: {}", err.to_str(), msg),
         }
     }
 }
@@ -81,22 +87,34 @@ impl Error {
         Error { repr: error }
     }
 
+    #[allow(clippy::inherent_to_string_shadow_display)]
     pub fn to_string(self) -> String {
         use ErrorType::*;
 
         match self.repr {
-            Simple(err) => format!("{}", err.to_str()),
+            Simple(err) => err.to_str().to_string(),
             SimpleMessage(err, &msg) => format!("{}: {}", err.to_str(), msg),
+            Message(err, msg) => format!("{}: {}", err.to_str(), msg),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        match &self.repr {
+            ErrorType::Simple(kind) => kind,
+            ErrorType::SimpleMessage(kind, _) => kind,
+            ErrorType::Message(kind, _) => kind,
         }
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.repr.to_string())
+        write!(f, "{}", self.repr)
     }
 }
 
+impl std::error::Error for Error {}
+
 impl From<ErrorKind> for Error {
     #[inline]
     fn from(kind: ErrorKind) -> Self {
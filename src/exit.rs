@@ -0,0 +1,66 @@
+use crate::audit::Severity;
+use crate::error::{Error, ErrorKind};
+use crate::report::{ProbeData, Report};
+
+/// Process exit status; when several apply the highest one is reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitCode {
+    /// Every document was probed and nothing reached the failing severity
+    Success,
+    /// Audit, image, outline, social card or structured data findings at or
+    /// above the failing severity, or broken links
+    Findings,
+    /// Invalid arguments, options or configuration
+    Usage,
+    /// A document could not be fetched, read or written
+    Failure,
+}
+
+// === impl ExitCode ===
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        match self {
+            ExitCode::Success => 0,
+            ExitCode::Findings => 1,
+            ExitCode::Usage => 2,
+            ExitCode::Failure => 3,
+        }
+    }
+
+    /// Status warranted by a single report
+    pub fn from_report(report: &Report, fail_on: Severity) -> ExitCode {
+        match report {
+            Report::Page(report) if report.is_failed() => ExitCode::Failure,
+            Report::Page(_) => ExitCode::Success,
+            Report::Probe(report) if report.is_failed() => ExitCode::Failure,
+            Report::Probe(report) => match report.data() {
+                Some(ProbeData::LinkChecks(checks)) if checks.iter().any(|c| c.is_broken()) => {
+                    ExitCode::Findings
+                }
+                Some(data) if data.findings().iter().any(|f| f.severity() >= fail_on) => {
+                    ExitCode::Findings
+                }
+                _ => ExitCode::Success,
+            },
+        }
+    }
+}
+
+impl From<&ErrorKind> for ExitCode {
+    fn from(kind: &ErrorKind) -> Self {
+        use ErrorKind::*;
+
+        match kind {
+            InvalidConfig | InvalidHtmlTag | InvalidInput | InvalidParameters | InvalidPath
+            | InvalidSearch | InvalidUrl => ExitCode::Usage,
+            _ => ExitCode::Failure,
+        }
+    }
+}
+
+impl From<&Error> for ExitCode {
+    fn from(err: &Error) -> Self {
+        ExitCode::from(err.kind())
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::html::{Extract, HtmlParser, Selection};
+use crate::utils::Result;
+
+/// Prefix of the columns extracted values are reported under, keeping them
+/// apart from the columns of the report itself
+pub const COLUMN_PREFIX: &str = "extract.";
+
+/// Named value pulled out of every probed or crawled page, configured as an
+/// `[[extract]]` table
+///
+/// ```toml
+/// [[extract]]
+/// name = "price"
+/// selector = "[itemprop=price]"
+/// attr = "content"
+/// regex = "([0-9.]+)"
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtractRule {
+    /// Names the column the values are reported under, `extract.<name>`
+    pub name: String,
+    pub selector: String,
+    /// Attribute taken from each match instead of its text
+    pub attr: Option<String>,
+    /// Keeps the first capture group, or the whole match when there is none,
+    /// of the values matching this expression; other values are dropped
+    pub regex: Option<String>,
+}
+
+/// Values a single rule pulled out of a document
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Extracted {
+    name: String,
+    values: Vec<String>,
+}
+
+/// Runs every extraction rule against a document
+#[derive(Debug, Default)]
+pub struct Extractor {
+    rules: Vec<(String, Selection, Option<Regex>)>,
+}
+
+// === impl Extracted ===
+
+impl Extracted {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Column the values are reported under
+    pub fn column(&self) -> String {
+        format!("{}{}", COLUMN_PREFIX, self.name)
+    }
+
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+}
+
+// === impl Extractor ===
+
+impl Extractor {
+    /// Fails when a selector or expression is invalid, or a name is used
+    /// twice; rules come from the configuration file, so it is the one at
+    /// fault
+    pub fn new(rules: &[ExtractRule]) -> Result<Extractor> {
+        let mut names = HashSet::new();
+        let mut compiled = Vec::with_capacity(rules.len());
+        for rule in rules {
+            if !names.insert(rule.name.as_str()) {
+                return Err(Extractor::invalid(format!(
+                    "extract rule {}; name is already taken",
+                    rule.name
+                )));
+            }
+            let extract = match &rule.attr {
+                Some(attr) => Extract::Attr(attr.clone()),
+                None => Extract::Text,
+            };
+            let regex = match &rule.regex {
+                Some(regex) => Some(Regex::new(regex).map_err(|err| {
+                    Extractor::invalid(format!("extract rule {}; {}", rule.name, err))
+                })?),
+                None => None,
+            };
+            let selection = Selection::new(&rule.selector, extract).map_err(|_| {
+                Extractor::invalid(format!(
+                    "extract rule {}; {} is not a valid css selector",
+                    rule.name, rule.selector
+                ))
+            })?;
+            compiled.push((rule.name.clone(), selection, regex));
+        }
+        Ok(Extractor { rules: compiled })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Values of every rule, in the order the rules were given
+    pub fn run<P: HtmlParser>(&self, parser: &P) -> Result<Vec<Extracted>> {
+        self.rules
+            .iter()
+            .map(|(name, selection, regex)| {
+                let mut values = parser.select(selection)?;
+                if let Some(regex) = regex {
+                    values = values
+                        .iter()
+                        .filter_map(|value| {
+                            let captures = regex.captures(value)?;
+                            captures.get(1).or_else(|| captures.get(0))
+                        })
+                        .map(|capture| capture.as_str().to_string())
+                        .collect();
+                }
+                Ok(Extracted {
+                    name: name.clone(),
+                    values,
+                })
+            })
+            .collect()
+    }
+
+    /// An empty value for every rule, for documents that could not be read
+    pub fn blank(&self) -> Vec<Extracted> {
+        self.rules
+            .iter()
+            .map(|(name, _, _)| Extracted {
+                name: name.clone(),
+                values: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn invalid(message: String) -> Error {
+        Error::new(ErrorType::Message(ErrorKind::InvalidConfig, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::html::Html;
+
+    const PAGE: &str = "<span class=price>12.50 EUR</span><span class=price>free</span>
+        <span class=sku>SKU 42-7</span><meta name=author content='Ada Lovelace'>";
+
+    fn rule(name: &str, selector: &str, attr: Option<&str>, regex: Option<&str>) -> ExtractRule {
+        ExtractRule {
+            name: name.to_string(),
+            selector: selector.to_string(),
+            attr: attr.map(str::to_string),
+            regex: regex.map(str::to_string),
+        }
+    }
+
+    fn extract(rules: &[ExtractRule]) -> Vec<Extracted> {
+        Extractor::new(rules).unwrap().run(&Html::inline(PAGE, None)).unwrap()
+    }
+
+    #[test]
+    fn values_are_the_text_or_attribute_of_every_match() {
+        let extracted = extract(&[
+            rule("price", ".price", None, None),
+            rule("author", "meta[name=author]", Some("content"), None),
+            rule("missing", "h1", None, None),
+        ]);
+
+        let values = extracted.iter().map(|e| (e.name(), e.values())).collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                ("price", &["12.50 EUR".to_string(), "free".to_string()][..]),
+                ("author", &["Ada Lovelace".to_string()][..]),
+                ("missing", &[][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn regex_keeps_the_first_capture_group_or_the_whole_match() {
+        let extracted = extract(&[
+            rule("price", ".price", None, Some(r"([0-9.]+) EUR")),
+            rule("sku", ".sku", None, Some(r"[0-9]+")),
+        ]);
+
+        assert_eq!(extracted[0].values(), ["12.50"]);
+        assert_eq!(extracted[1].values(), ["42"]);
+    }
+
+    #[test]
+    fn values_not_matching_the_regex_are_dropped() {
+        let extracted = extract(&[rule("price", ".price", None, Some("^[0-9]+$"))]);
+        assert!(extracted[0].values().is_empty());
+    }
+
+    #[test]
+    fn values_are_reported_under_prefixed_columns() {
+        let extractor = Extractor::new(&[rule("price", ".price", None, None)]).unwrap();
+        let blank = extractor.blank();
+
+        assert_eq!(blank[0].column(), "extract.price");
+        assert!(blank[0].values().is_empty());
+    }
+
+    #[test]
+    fn invalid_rules_in_the_configuration_are_configuration_errors() {
+        let config = "[[extract]]\nname = \"price\"\nselector = \".price\"\nregex = \"([0-9\"\n"
+            .parse::<Config>()
+            .unwrap();
+        let err = Extractor::new(&config.extract).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidConfig));
+        assert!(err.to_string().contains("extract rule price"));
+
+        let rules = [rule("a", "[", None, None)];
+        assert!(matches!(Extractor::new(&rules).unwrap_err().kind(), ErrorKind::InvalidConfig));
+
+        let rules = [rule("a", "p", None, None), rule("a", "h1", None, None)];
+        assert!(matches!(Extractor::new(&rules).unwrap_err().kind(), ErrorKind::InvalidConfig));
+    }
+}
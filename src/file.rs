@@ -1,11 +1,15 @@
+use std::io;
 use std::str;
 
 use tokio::fs::File as TokioFile;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
-use crate::error::{Error, ErrorKind};
+use crate::error::{Error, ErrorKind, ErrorType};
 use crate::utils::Result;
 
+/// Path used to refer to standard input
+pub const STDIN: &str = "-";
+
 pub struct File {
     text: String,
 }
@@ -17,24 +21,58 @@ impl File {
         }
     }
 
+    /// Reads the entire contents of the reader into `buf`
+    pub async fn read<R: AsyncRead + Unpin>(mut reader: R, mut buf: String) -> Result<String> {
+        match reader.read_to_string(&mut buf).await {
+            Ok(_) => Ok(buf),
+            Err(err) => Err(File::io_error(err.to_string(), &err)),
+        }
+    }
+
+    /// Returns each non-empty, non-comment (`#`) line of the file at `path`;
+    /// a path of `-` reads from stdin
+    pub async fn lines(path: &str) -> Result<Vec<String>> {
+        let text = if path == STDIN {
+            File::read(tokio::io::stdin(), String::new()).await?
+        } else {
+            File::from(path, String::new()).await?
+        };
+        Ok(text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect())
+    }
+
     pub async fn from(path: &str, mut buf: String) -> Result<String> {
-        match TokioFile::open(path).await {
-            Ok(mut f) => {
-                if let Ok(_) = f.read_to_string(&mut buf).await {
-                    Ok(buf)
-                } else {
-                    Err(Error::from(ErrorKind::InvalidUtf8))
-                }
-            }
-            Err(_) => Err(Error::from(ErrorKind::NotFound)),
+        let read = match TokioFile::open(path).await {
+            Ok(mut f) => f.read_to_string(&mut buf).await,
+            Err(err) => Err(err),
+        };
+        match read {
+            Ok(_) => Ok(buf),
+            Err(err) => Err(File::io_error(format!("{}; {}", path, err), &err)),
         }
     }
 
+    /// Error of the kind matching what went wrong; text that is not UTF-8 is
+    /// reported by `read_to_string` as invalid data
+    fn io_error(message: String, err: &io::Error) -> Error {
+        let kind = match err.kind() {
+            io::ErrorKind::NotFound => ErrorKind::FileNotFound,
+            io::ErrorKind::InvalidData => ErrorKind::InvalidUtf8,
+            _ => ErrorKind::Io,
+        };
+        Error::new(ErrorType::Message(kind, message))
+    }
+
     pub fn text(&self) -> &str {
         &self.text
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for File {
     fn default() -> Self {
         File { text: String::new() }
@@ -1,35 +1,74 @@
+use std::cell::OnceCell;
 use std::fmt::{Display, Formatter};
 use std::ops::Deref;
 use std::str;
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
 use bytes::Bytes;
 use reqwest;
+use reqwest::{Method, Url};
 use select::document::Document;
+use select::node::Node;
 use select::predicate::Predicate;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncRead;
 
-use crate::error::{Error, ErrorKind};
+use crate::error::{Error, ErrorKind, ErrorType};
 use crate::file::File;
-use crate::parse::{FromPath, FromUrl};
+use crate::http::{self, Fetched, ResponseMeta};
+use crate::parse::{FromPath, FromReader, FromUrl};
+use crate::schema::Item;
+use crate::social::Social;
 use crate::utils::Result;
 
 #[derive(Debug)]
 pub enum HtmlAttribute {
     A,
+    Alt,
     Content,
+    Height,
     Href,
+    ItemId,
+    ItemProp,
+    ItemScope,
+    ItemType,
+    Loading,
     Name,
+    Property,
+    Rel,
+    Resource,
+    Src,
+    Srcset,
+    Target,
+    Type,
+    TypeOf,
+    Width,
 }
 
 impl Display for HtmlAttribute {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             HtmlAttribute::A => write!(f, "a"),
+            HtmlAttribute::Alt => write!(f, "alt"),
             HtmlAttribute::Content => write!(f, "content"),
+            HtmlAttribute::Height => write!(f, "height"),
             HtmlAttribute::Href => write!(f, "href"),
+            HtmlAttribute::ItemId => write!(f, "itemid"),
+            HtmlAttribute::ItemProp => write!(f, "itemprop"),
+            HtmlAttribute::ItemScope => write!(f, "itemscope"),
+            HtmlAttribute::ItemType => write!(f, "itemtype"),
+            HtmlAttribute::Loading => write!(f, "loading"),
             HtmlAttribute::Name => write!(f, "name"),
+            HtmlAttribute::Property => write!(f, "property"),
+            HtmlAttribute::Rel => write!(f, "rel"),
+            HtmlAttribute::Resource => write!(f, "resource"),
+            HtmlAttribute::Src => write!(f, "src"),
+            HtmlAttribute::Srcset => write!(f, "srcset"),
+            HtmlAttribute::Target => write!(f, "target"),
+            HtmlAttribute::Type => write!(f, "type"),
+            HtmlAttribute::TypeOf => write!(f, "typeof"),
+            HtmlAttribute::Width => write!(f, "width"),
         }
     }
 }
@@ -38,9 +77,25 @@ impl From<HtmlAttribute> for &str {
     fn from(attr: HtmlAttribute) -> Self {
         match attr {
             HtmlAttribute::A => "a",
+            HtmlAttribute::Alt => "alt",
             HtmlAttribute::Content => "content",
+            HtmlAttribute::Height => "height",
             HtmlAttribute::Href => "href",
+            HtmlAttribute::ItemId => "itemid",
+            HtmlAttribute::ItemProp => "itemprop",
+            HtmlAttribute::ItemScope => "itemscope",
+            HtmlAttribute::ItemType => "itemtype",
+            HtmlAttribute::Loading => "loading",
             HtmlAttribute::Name => "name",
+            HtmlAttribute::Property => "property",
+            HtmlAttribute::Rel => "rel",
+            HtmlAttribute::Resource => "resource",
+            HtmlAttribute::Src => "src",
+            HtmlAttribute::Srcset => "srcset",
+            HtmlAttribute::Target => "target",
+            HtmlAttribute::Type => "type",
+            HtmlAttribute::TypeOf => "typeof",
+            HtmlAttribute::Width => "width",
         }
     }
 }
@@ -51,38 +106,98 @@ impl FromStr for HtmlAttribute {
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         match s {
             "a" => Ok(HtmlAttribute::A),
+            "alt" => Ok(HtmlAttribute::Alt),
             "content" => Ok(HtmlAttribute::Content),
+            "height" => Ok(HtmlAttribute::Height),
             "href" => Ok(HtmlAttribute::Href),
+            "itemid" => Ok(HtmlAttribute::ItemId),
+            "itemprop" => Ok(HtmlAttribute::ItemProp),
+            "itemscope" => Ok(HtmlAttribute::ItemScope),
+            "itemtype" => Ok(HtmlAttribute::ItemType),
+            "loading" => Ok(HtmlAttribute::Loading),
             "name" => Ok(HtmlAttribute::Name),
+            "property" => Ok(HtmlAttribute::Property),
+            "rel" => Ok(HtmlAttribute::Rel),
+            "resource" => Ok(HtmlAttribute::Resource),
+            "src" => Ok(HtmlAttribute::Src),
+            "srcset" => Ok(HtmlAttribute::Srcset),
+            "target" => Ok(HtmlAttribute::Target),
+            "type" => Ok(HtmlAttribute::Type),
+            "typeof" => Ok(HtmlAttribute::TypeOf),
+            "width" => Ok(HtmlAttribute::Width),
             _ => Err(Error::from(ErrorKind::InvalidParameters)),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HtmlTag {
+    Article,
+    Aside,
+    Base,
+    Body,
+    Footer,
     H1,
     H2,
     H3,
     H4,
     H5,
     H6,
+    Header,
+    Img,
     Invalid,
+    Link,
+    Main,
     Meta,
+    Nav,
+    Picture,
+    Script,
+    Section,
+    Source,
     Title,
 }
 
+impl HtmlTag {
+    /// Every header level, in order
+    pub const HEADERS: [HtmlTag; 6] = [
+        HtmlTag::H1,
+        HtmlTag::H2,
+        HtmlTag::H3,
+        HtmlTag::H4,
+        HtmlTag::H5,
+        HtmlTag::H6,
+    ];
+
+    pub fn is_header(&self) -> bool {
+        HtmlTag::HEADERS.contains(self)
+    }
+}
+
 impl Display for HtmlTag {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            HtmlTag::Article => write!(f, "article"),
+            HtmlTag::Aside => write!(f, "aside"),
+            HtmlTag::Base => write!(f, "base"),
+            HtmlTag::Body => write!(f, "body"),
+            HtmlTag::Footer => write!(f, "footer"),
             HtmlTag::H1 => write!(f, "h1"),
             HtmlTag::H2 => write!(f, "h2"),
             HtmlTag::H3 => write!(f, "h3"),
             HtmlTag::H4 => write!(f, "h4"),
             HtmlTag::H5 => write!(f, "h5"),
             HtmlTag::H6 => write!(f, "h6"),
+            HtmlTag::Header => write!(f, "header"),
+            HtmlTag::Img => write!(f, "img"),
             HtmlTag::Invalid => write!(f, "invalid"),
+            HtmlTag::Link => write!(f, "link"),
+            HtmlTag::Main => write!(f, "main"),
             HtmlTag::Meta => write!(f, "meta"),
+            HtmlTag::Nav => write!(f, "nav"),
+            HtmlTag::Picture => write!(f, "picture"),
+            HtmlTag::Script => write!(f, "script"),
+            HtmlTag::Section => write!(f, "section"),
+            HtmlTag::Source => write!(f, "source"),
             HtmlTag::Title => write!(f, "title"),
         }
     }
@@ -93,16 +208,30 @@ impl FromStr for HtmlTag {
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         match s {
-            "1" => Ok(HtmlTag::H1),
-            "2" => Ok(HtmlTag::H2),
-            "3" => Ok(HtmlTag::H3),
-            "4" => Ok(HtmlTag::H4),
-            "5" => Ok(HtmlTag::H5),
-            "6" => Ok(HtmlTag::H6),
+            "article" => Ok(HtmlTag::Article),
+            "aside" => Ok(HtmlTag::Aside),
+            "base" => Ok(HtmlTag::Base),
+            "body" => Ok(HtmlTag::Body),
+            "footer" => Ok(HtmlTag::Footer),
+            "1" | "h1" => Ok(HtmlTag::H1),
+            "2" | "h2" => Ok(HtmlTag::H2),
+            "3" | "h3" => Ok(HtmlTag::H3),
+            "4" | "h4" => Ok(HtmlTag::H4),
+            "5" | "h5" => Ok(HtmlTag::H5),
+            "6" | "h6" => Ok(HtmlTag::H6),
+            "header" => Ok(HtmlTag::Header),
+            "img" => Ok(HtmlTag::Img),
             "invalid" => Ok(HtmlTag::Invalid),
+            "link" => Ok(HtmlTag::Link),
+            "main" => Ok(HtmlTag::Main),
             "meta" => Ok(HtmlTag::Meta),
+            "nav" => Ok(HtmlTag::Nav),
+            "picture" => Ok(HtmlTag::Picture),
+            "script" => Ok(HtmlTag::Script),
+            "section" => Ok(HtmlTag::Section),
+            "source" => Ok(HtmlTag::Source),
             "title" => Ok(HtmlTag::Title),
-            _ => Err(Error::from(ErrorKind::InvalidParameters)),
+            _ => Err(Error::new(ErrorType::Message(ErrorKind::InvalidHtmlTag, s.to_string()))),
         }
     }
 }
@@ -110,14 +239,28 @@ impl FromStr for HtmlTag {
 impl From<HtmlTag> for &str {
     fn from(tag: HtmlTag) -> Self {
         match tag {
+            HtmlTag::Article => "article",
+            HtmlTag::Aside => "aside",
+            HtmlTag::Base => "base",
+            HtmlTag::Body => "body",
+            HtmlTag::Footer => "footer",
             HtmlTag::H1 => "1",
             HtmlTag::H2 => "2",
             HtmlTag::H3 => "3",
             HtmlTag::H4 => "4",
             HtmlTag::H5 => "5",
             HtmlTag::H6 => "6",
+            HtmlTag::Header => "header",
+            HtmlTag::Img => "img",
             HtmlTag::Invalid => "invalid",
+            HtmlTag::Link => "link",
+            HtmlTag::Main => "main",
             HtmlTag::Meta => "meta",
+            HtmlTag::Nav => "nav",
+            HtmlTag::Picture => "picture",
+            HtmlTag::Script => "script",
+            HtmlTag::Section => "section",
+            HtmlTag::Source => "source",
             HtmlTag::Title => "title",
         }
     }
@@ -137,6 +280,16 @@ impl From<HtmlTag> for u8 {
     }
 }
 
+/// Heading of a document, in document order unlike `Headers`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Heading {
+    level: u8,
+    text: String,
+    /// Place among the document's headings, starting at 1
+    position: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Headers {
     H1(Vec<String>),
     H2(Vec<String>),
@@ -158,6 +311,19 @@ impl Headers {
             _ => Headers::Invalid(Vec::new()),
         }
     }
+
+    /// Returns the tag the headers were collected from
+    pub fn tag(&self) -> HtmlTag {
+        match self {
+            Headers::H1(_) => HtmlTag::H1,
+            Headers::H2(_) => HtmlTag::H2,
+            Headers::H3(_) => HtmlTag::H3,
+            Headers::H4(_) => HtmlTag::H4,
+            Headers::H5(_) => HtmlTag::H5,
+            Headers::H6(_) => HtmlTag::H6,
+            Headers::Invalid(_) => HtmlTag::Invalid,
+        }
+    }
 }
 impl Deref for Headers {
     type Target = Vec<String>;
@@ -175,76 +341,695 @@ impl Deref for Headers {
     }
 }
 
+/// Where a link points relative to the page it was found on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// Another location within the same page
+    Anchor,
+    /// A page on a different host
+    External,
+    /// A page on the same host
+    Internal,
+    Javascript,
+    Mailto,
+    Tel,
+    /// Any other scheme, such as `ftp:` or `data:`
+    Other,
+}
+
+/// Link found in a document
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Link {
+    href: String,
+    url: String,
+    text: String,
+    rel: Vec<String>,
+    target: Option<String>,
+    kind: LinkKind,
+}
+
+/// Image found in a document
+///
+/// Each `<source>` of a `<picture>` is listed before the `<img>` it falls
+/// back to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Image {
+    tag: String,
+    src: Option<String>,
+    srcset: Option<String>,
+    url: Option<String>,
+    /// Media type of a `<source>`, such as `image/webp`
+    media_type: Option<String>,
+    alt: Option<String>,
+    width: Option<String>,
+    height: Option<String>,
+    loading: Option<String>,
+    in_picture: bool,
+}
+
+/// What is taken from each element a css selector matches
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Extract {
+    /// Text content, with whitespace collapsed
+    Text,
+    InnerHtml,
+    /// Value of the named attribute; elements without it are left out
+    Attr(String),
+}
+
+/// Css selector along with what to take from each element it matches
+///
+/// The selector is parsed once, when the selection is built or read from a
+/// request.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "SelectionDef", into = "SelectionDef")]
+pub struct Selection {
+    selector: String,
+    extract: Extract,
+    parsed: scraper::Selector,
+}
+
+/// What a `Selection` is serialized as; the parsed selector is rebuilt
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct SelectionDef {
+    selector: String,
+    extract: Extract,
+}
+
+/// Robots directives, such as `noindex` or `nofollow`, given to every
+/// crawler or to a single one
+///
+/// Read from `<meta name="robots">`, crawler specific meta tags such as
+/// `<meta name="googlebot">`, and `X-Robots-Tag` headers.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RobotsMeta {
+    agent: Option<String>,
+    directives: Vec<String>,
+}
+
+/// Matches anchors nested within any of the given tags
+pub struct AnchorWithin<'a> {
+    tags: &'a [HtmlTag],
+}
+
+impl Display for LinkKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkKind::Anchor => write!(f, "anchor"),
+            LinkKind::External => write!(f, "external"),
+            LinkKind::Internal => write!(f, "internal"),
+            LinkKind::Javascript => write!(f, "javascript"),
+            LinkKind::Mailto => write!(f, "mailto"),
+            LinkKind::Tel => write!(f, "tel"),
+            LinkKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+impl LinkKind {
+    /// Classifies `href`, already resolved to `url`, relative to `page`, the
+    /// url relative links were resolved against
+    pub fn classify(href: &str, url: &str, page: Option<&Url>) -> LinkKind {
+        if href.trim_start().starts_with('#') {
+            return LinkKind::Anchor;
+        }
+        let url = match Url::parse(url) {
+            Ok(url) => url,
+            // still relative; nothing to resolve it against but the page itself
+            Err(_) => return LinkKind::Internal,
+        };
+        match url.scheme() {
+            "javascript" => LinkKind::Javascript,
+            "mailto" => LinkKind::Mailto,
+            "tel" => LinkKind::Tel,
+            "http" | "https" | "file" => match page {
+                Some(page) if url.fragment().is_some() && Link::same_document(&url, page) => {
+                    LinkKind::Anchor
+                }
+                Some(page) if url.host_str() == page.host_str() => LinkKind::Internal,
+                _ => LinkKind::External,
+            },
+            _ => LinkKind::Other,
+        }
+    }
+}
+
+// === impl Link ===
+
+impl Link {
+    pub fn new(
+        href: &str,
+        url: String,
+        text: &str,
+        rel: Option<&str>,
+        target: Option<&str>,
+        page: Option<&Url>,
+    ) -> Link {
+        Link {
+            kind: LinkKind::classify(href, &url, page),
+            href: href.to_string(),
+            url,
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            rel: rel
+                .map(|rel| rel.split_whitespace().map(|r| r.to_lowercase()).collect())
+                .unwrap_or_default(),
+            target: target.map(str::to_string),
+        }
+    }
+
+    /// The `href` attribute exactly as written
+    pub fn href(&self) -> &str {
+        &self.href
+    }
+
+    /// Absolute, normalized url the link points to
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// `rel` values such as `nofollow`, `sponsored` or `ugc`, lowercased
+    pub fn rel(&self) -> &[String] {
+        &self.rel
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn kind(&self) -> LinkKind {
+        self.kind
+    }
+
+    pub fn is_nofollow(&self) -> bool {
+        self.rel.iter().any(|r| r == "nofollow")
+    }
+
+    fn same_document(url: &Url, page: &Url) -> bool {
+        let mut url = url.clone();
+        let mut page = page.clone();
+        url.set_fragment(None);
+        page.set_fragment(None);
+        url == page
+    }
+}
+
+// === impl Heading ===
+
+impl Heading {
+    /// Whitespace within `text` is collapsed to single spaces
+    pub fn new(level: u8, text: &str, position: usize) -> Heading {
+        Heading {
+            level,
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            position,
+        }
+    }
+
+    /// 1 for `h1` through 6 for `h6`
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn tag(&self) -> HtmlTag {
+        HtmlTag::HEADERS.get(self.level as usize - 1).copied().unwrap_or(HtmlTag::Invalid)
+    }
+}
+
+// === impl Image ===
+
+impl Image {
+    /// Reads an `<img>` or `<source>`; `url` is its `src`, or the first
+    /// candidate of its `srcset`, resolved against the document's base url
+    pub fn new(node: &Node, url: Option<String>) -> Image {
+        let attr = |attr: HtmlAttribute| node.attr(attr.into()).map(str::to_string);
+        Image {
+            tag: node.name().unwrap_or_default().to_string(),
+            src: attr(HtmlAttribute::Src),
+            srcset: attr(HtmlAttribute::Srcset),
+            url,
+            media_type: attr(HtmlAttribute::Type),
+            alt: attr(HtmlAttribute::Alt),
+            width: attr(HtmlAttribute::Width),
+            height: attr(HtmlAttribute::Height),
+            loading: attr(HtmlAttribute::Loading),
+            in_picture: node.parent().and_then(|p| p.name()) == Some(HtmlTag::Picture.into()),
+        }
+    }
+
+    /// `img` or `source`
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn src(&self) -> Option<&str> {
+        self.src.as_deref()
+    }
+
+    pub fn srcset(&self) -> Option<&str> {
+        self.srcset.as_deref()
+    }
+
+    /// Absolute url of the image, when it has one
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn media_type(&self) -> Option<&str> {
+        self.media_type.as_deref()
+    }
+
+    /// The `alt` text; `Some("")` marks a decorative image
+    pub fn alt(&self) -> Option<&str> {
+        self.alt.as_deref()
+    }
+
+    pub fn width(&self) -> Option<&str> {
+        self.width.as_deref()
+    }
+
+    pub fn height(&self) -> Option<&str> {
+        self.height.as_deref()
+    }
+
+    pub fn loading(&self) -> Option<&str> {
+        self.loading.as_deref()
+    }
+
+    /// True for the `<source>` and `<img>` elements of a `<picture>`
+    pub fn in_picture(&self) -> bool {
+        self.in_picture
+    }
+}
+
+// === impl Selection ===
+
+impl Selection {
+    /// Fails when `selector` is not valid css
+    pub fn new(selector: &str, extract: Extract) -> Result<Selection> {
+        Ok(Selection {
+            selector: selector.to_string(),
+            extract,
+            parsed: Selection::parse(selector)?,
+        })
+    }
+
+    pub fn selector(&self) -> &str {
+        &self.selector
+    }
+
+    pub fn extract(&self) -> &Extract {
+        &self.extract
+    }
+
+    /// Runs the selection against `dom`, in document order
+    pub fn run(&self, dom: &scraper::Html) -> Result<Vec<String>> {
+        Ok(dom
+            .select(&self.parsed)
+            .filter_map(|element| match &self.extract {
+                Extract::Text => {
+                    let text = element.text().collect::<String>();
+                    Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+                Extract::InnerHtml => Some(element.inner_html()),
+                Extract::Attr(name) => element.value().attr(name).map(str::to_string),
+            })
+            .collect())
+    }
+
+    fn parse(selector: &str) -> Result<scraper::Selector> {
+        scraper::Selector::parse(selector).map_err(|_| {
+            Error::new(ErrorType::Message(
+                ErrorKind::InvalidSearch,
+                format!("{}; not a valid css selector", selector),
+            ))
+        })
+    }
+}
+
+impl TryFrom<SelectionDef> for Selection {
+    type Error = Error;
+
+    fn try_from(def: SelectionDef) -> Result<Selection> {
+        Selection::new(&def.selector, def.extract)
+    }
+}
+
+impl From<Selection> for SelectionDef {
+    fn from(selection: Selection) -> SelectionDef {
+        SelectionDef {
+            selector: selection.selector,
+            extract: selection.extract,
+        }
+    }
+}
+
+// === impl RobotsMeta ===
+
+impl RobotsMeta {
+    /// Meta names that carry robots directives; `robots` applies to every crawler
+    pub const NAMES: [&'static str; 9] = [
+        "robots",
+        "googlebot",
+        "googlebot-news",
+        "googlebot-image",
+        "bingbot",
+        "msnbot",
+        "slurp",
+        "yandex",
+        "duckduckbot",
+    ];
+
+    /// Directives in `content` given to `agent`, or to every crawler when
+    /// `agent` is `robots` or not given
+    pub fn new(agent: Option<&str>, content: &str) -> RobotsMeta {
+        RobotsMeta {
+            agent: agent.map(str::to_lowercase).filter(|agent| agent != "robots"),
+            directives: content
+                .split(',')
+                .map(|d| d.trim().to_lowercase())
+                .filter(|d| !d.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Parses an `X-Robots-Tag` header value, such as `noindex, nofollow` or
+    /// `googlebot: noindex`
+    pub fn from_header(value: &str) -> RobotsMeta {
+        match value.split_once(':') {
+            // directives such as `unavailable_after: <date>` also contain a colon
+            Some((agent, content))
+                if !agent.trim().is_empty()
+                    && !agent.trim().contains([',', ' ', '_'])
+                    && !agent.trim().starts_with("max-") =>
+            {
+                RobotsMeta::new(Some(agent.trim()), content)
+            }
+            _ => RobotsMeta::new(None, value),
+        }
+    }
+
+    /// Crawler the directives are given to; `None` for every crawler
+    pub fn agent(&self) -> Option<&str> {
+        self.agent.as_deref()
+    }
+
+    /// Lowercased directives, in the order written
+    pub fn directives(&self) -> &[String] {
+        &self.directives
+    }
+
+    pub fn applies_to(&self, bot: &str) -> bool {
+        match &self.agent {
+            Some(agent) => agent.eq_ignore_ascii_case(bot),
+            None => true,
+        }
+    }
+
+    /// True when the directives keep the page out of the index; `none`
+    /// implies `noindex`
+    pub fn is_noindex(&self) -> bool {
+        self.directives.iter().any(|d| d == "noindex" || d == "none")
+    }
+}
+
+impl Display for RobotsMeta {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.agent {
+            Some(agent) => write!(f, "{}: {}", agent, self.directives.join(", ")),
+            None => write!(f, "{}", self.directives.join(", ")),
+        }
+    }
+}
+
+impl<'a> AnchorWithin<'a> {
+    pub fn new(tags: &'a [HtmlTag]) -> AnchorWithin<'a> {
+        AnchorWithin { tags }
+    }
+}
+
+impl Predicate for AnchorWithin<'_> {
+    fn matches(&self, node: &Node) -> bool {
+        if node.name() != Some(HtmlAttribute::A.into()) {
+            return false;
+        }
+        let mut parent = node.parent();
+        while let Some(n) = parent {
+            if let Some(name) = n.name() {
+                if self.tags.iter().any(|tag| tag.to_string() == name) {
+                    return true;
+                }
+            }
+            parent = n.parent();
+        }
+        false
+    }
+}
+
 pub trait HtmlDocument {
     fn bytes(&self) -> Bytes;
-    fn document(&self) -> Result<Document>;
+    fn document(&self) -> Result<&Document>;
+    /// The same document as parsed by `scraper`, for css selector queries
+    fn dom(&self) -> &scraper::Html;
     fn text(&self) -> Result<String>;
+    /// Location the document was read from, when it has one
+    fn url(&self) -> Option<&Url>;
 }
 
 pub trait HtmlParser {
+    /// Returns the `href` of every `<link rel="canonical">`, resolved against
+    /// the document's base url
+    fn canonicals(&self) -> Result<Vec<String>>;
     fn descriptions(&self) -> Result<Vec<String>>;
     fn header(&self, header: HtmlTag) -> Result<Headers>;
-    fn links<P: Predicate>(&self, predicate: P) -> Result<Vec<String>>;
+    /// Returns every `h1` through `h6`, in document order
+    fn headings(&self) -> Result<Vec<Heading>>;
+    /// Returns every `<img>`, and every `<source>` of a `<picture>`, in
+    /// document order
+    fn images(&self) -> Result<Vec<Image>>;
+    /// Returns the contents of every `<script type="application/ld+json">`,
+    /// unparsed
+    fn json_ld(&self) -> Result<Vec<String>>;
+    fn links<P: Predicate>(&self, predicate: P) -> Result<Vec<Link>>;
+    /// Returns the `href` of every matching element exactly as written
+    ///
+    /// `links` resolves these against the document's base url instead.
+    fn hrefs<P: Predicate>(&self, predicate: P) -> Result<Vec<String>>;
+    /// Returns every top level microdata item, with the items nested within
+    fn microdata(&self) -> Result<Vec<Item>>;
     fn page_title(&self) -> Result<Vec<String>>;
+    /// Returns every top level RDFa item, with the items nested within
+    fn rdfa(&self) -> Result<Vec<Item>>;
+    /// Returns the directives of every robots meta tag, generic or crawler
+    /// specific; see `RobotsMeta::NAMES`
+    fn robots(&self) -> Result<Vec<RobotsMeta>>;
+    /// Returns what `selection` takes from every element it matches
+    fn select(&self, selection: &Selection) -> Result<Vec<String>>;
+    /// Returns the Open Graph and Twitter Card properties, read from either
+    /// the `property` or the `name` of each meta tag
+    fn social(&self) -> Result<Social>;
 }
 
+/// Html document, parsed once when it is read
+///
+/// Every query borrows the same tree. The tree cannot be sent across
+/// threads, so documents are read through an `HtmlLoader` and queried once
+/// no more awaiting is needed.
 pub struct Html {
-    html: Arc<Mutex<Bytes>>,
+    html: Bytes,
+    document: Document,
+    dom: OnceCell<scraper::Html>,
+    response: Option<ResponseMeta>,
+    url: Option<Url>,
 }
 
-impl Default for Html {
-    fn default() -> Self {
-        Self {
-            html: Arc::new(Mutex::new(Bytes::from(""))),
-        }
+/// Reads html documents from files, readers and urls into `Html`
+#[derive(Default)]
+pub struct HtmlLoader {
+    client: http::Client,
+}
+
+impl Html {
+    fn parse(html: Bytes, response: Option<ResponseMeta>, url: Option<Url>) -> Result<Html> {
+        let document =
+            Document::from_read(&html[..]).map_err(|_| Error::from(ErrorKind::Document))?;
+        Ok(Html {
+            html,
+            document,
+            dom: OnceCell::new(),
+            response,
+            url,
+        })
+    }
+
+    /// Response the document was read from, if fetched
+    pub fn response(&self) -> Option<&ResponseMeta> {
+        self.response.as_ref()
+    }
+
+    /// Status code of the response the document was read from, if fetched
+    pub fn status(&self) -> Option<u16> {
+        self.response.as_ref().map(ResponseMeta::status)
+    }
+}
+
+#[cfg(test)]
+impl Html {
+    /// Parses `html` as if it had been read from `url`, without a response
+    pub(crate) fn inline(html: &str, url: Option<&str>) -> crate::parse::Parse<Html> {
+        let url = url.map(|url| Url::parse(url).unwrap());
+        crate::parse::Parse::new(Html::parse(Bytes::from(html.to_string()), None, url).unwrap())
+    }
+}
+
+impl HtmlLoader {
+    /// Fetches remote documents with the given client
+    pub fn with_client(client: http::Client) -> Self {
+        Self { client }
     }
 }
 
 #[async_trait]
-impl FromPath for Html {
-    async fn from(&mut self, path: &str, capacity: usize) -> Result<Self> {
-        Ok(Self {
-            html: Arc::new(Mutex::new(Bytes::from(
-                File::from(path, String::with_capacity(capacity)).await?,
-            ))),
-        })
+impl FromPath for HtmlLoader {
+    type Output = Html;
+
+    async fn from(&mut self, path: &str, capacity: usize) -> Result<Html> {
+        let html = File::from(path, String::with_capacity(capacity)).await?;
+        let url = match tokio::fs::canonicalize(path).await {
+            Ok(path) => Url::from_file_path(path).ok(),
+            Err(_) => None,
+        };
+        Html::parse(Bytes::from(html), None, url)
     }
 }
 
 #[async_trait]
-impl FromUrl for Html {
-    async fn from(&mut self, url: &str) -> Result<Self> {
-        match reqwest::get(url).await {
-            Ok(resp) => {
-                if let Ok(doc) = resp.text().await {
-                    Ok(Self {
-                        html: Arc::new(Mutex::new(Bytes::from(doc))),
-                    })
-                } else {
-                    Err(Error::from(ErrorKind::Html))
-                }
-            }
-            Err(_) => Err(Error::from(ErrorKind::Http)),
-        }
+impl FromReader for HtmlLoader {
+    type Output = Html;
+
+    async fn read<R: AsyncRead + Unpin + Send>(
+        &mut self,
+        reader: R,
+        capacity: usize,
+    ) -> Result<Html> {
+        let html = File::read(reader, String::with_capacity(capacity)).await?;
+        Html::parse(Bytes::from(html), None, None)
+    }
+}
+
+#[async_trait]
+impl FromUrl for HtmlLoader {
+    type Output = Html;
+
+    async fn from(&mut self, url: &str) -> Result<Html> {
+        let Fetched { meta, body } = self.client.fetch(Method::GET, url).await?;
+        let url = Url::parse(meta.url()).ok();
+        Html::parse(Bytes::from(body), Some(meta), url)
     }
 }
 
 impl HtmlDocument for Html {
     fn bytes(&self) -> Bytes {
-        self.html.clone().lock().unwrap().to_vec().into()
+        self.html.clone()
     }
 
-    fn document(&self) -> Result<Document> {
-        match Document::from_read(&**self.html.clone().lock().unwrap()) {
-            Ok(doc) => Ok(doc),
-            Err(_) => Err(Error::from(ErrorKind::Document)),
-        }
+    fn document(&self) -> Result<&Document> {
+        Ok(&self.document)
+    }
+
+    fn dom(&self) -> &scraper::Html {
+        // only parsed for the few queries that need it
+        self.dom
+            .get_or_init(|| scraper::Html::parse_document(&String::from_utf8_lossy(&self.html)))
     }
 
     fn text(&self) -> Result<String> {
-        match str::from_utf8(&self.html.clone().lock().unwrap()) {
+        match str::from_utf8(&self.html) {
             Ok(text) => Ok(text.to_string()),
             Err(_) => Err(Error::from(ErrorKind::Parse)),
         }
     }
+
+    fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use select::predicate::Name;
+
+    fn classify(href: &str, page: &str) -> LinkKind {
+        let page = Url::parse(page).unwrap();
+        let url = page.join(href).map(String::from).unwrap_or_else(|_| href.to_string());
+        LinkKind::classify(href, &url, Some(&page))
+    }
+
+    #[test]
+    fn classify_tells_internal_from_external_links() {
+        assert_eq!(classify("/about", "http://site.test/"), LinkKind::Internal);
+        assert_eq!(classify("http://site.test/a?b=c", "http://site.test/"), LinkKind::Internal);
+        assert_eq!(classify("other.html#faq", "http://site.test/page.html"), LinkKind::Internal);
+        assert_eq!(classify("http://other.test/", "http://site.test/"), LinkKind::External);
+        assert_eq!(classify("//cdn.test/a.js", "http://site.test/"), LinkKind::External);
+        assert_eq!(classify("http://www.site.test/", "http://site.test/"), LinkKind::External);
+    }
+
+    #[test]
+    fn classify_keeps_the_same_host_internal_whatever_the_scheme() {
+        assert_eq!(classify("https://site.test/login", "http://site.test/"), LinkKind::Internal);
+        assert_eq!(classify("http://site.test/", "https://site.test/"), LinkKind::Internal);
+    }
+
+    #[test]
+    fn classify_recognizes_schemes_without_a_host() {
+        assert_eq!(classify("mailto:me@site.test", "http://site.test/"), LinkKind::Mailto);
+        assert_eq!(classify("tel:+15550100", "http://site.test/"), LinkKind::Tel);
+        assert_eq!(classify("javascript:void(0)", "http://site.test/"), LinkKind::Javascript);
+        assert_eq!(classify("ftp://site.test/a.zip", "http://site.test/"), LinkKind::Other);
+    }
+
+    #[test]
+    fn classify_finds_anchors_within_the_page() {
+        assert_eq!(classify("#top", "http://site.test/page.html"), LinkKind::Anchor);
+        assert_eq!(classify(" #top", "http://site.test/page.html"), LinkKind::Anchor);
+        assert_eq!(classify("page.html#faq", "http://site.test/page.html"), LinkKind::Anchor);
+        assert_eq!(LinkKind::classify("#top", "#top", None), LinkKind::Anchor);
+    }
+
+    #[test]
+    fn classify_keeps_unresolved_relative_links_internal() {
+        assert_eq!(LinkKind::classify("about.html", "about.html", None), LinkKind::Internal);
+        let url = "http://a.test/";
+        assert_eq!(LinkKind::classify(url, url, None), LinkKind::External);
+    }
+
+    #[test]
+    fn links_are_classified_against_the_base_url() {
+        let html = "<base href='https://cdn.test/docs/'>
+            <a href='guide.html'>Guide</a>
+            <a href='http://site.test/home'>Home</a>
+            <a href='#intro'>Intro</a>";
+        let page = Html::inline(html, Some("http://site.test/page.html"));
+        let links = page.links(Name("a")).unwrap();
+
+        assert_eq!(links[0].url(), "https://cdn.test/docs/guide.html");
+        assert_eq!(links[0].kind(), LinkKind::Internal);
+        assert_eq!(links[1].kind(), LinkKind::External);
+        assert_eq!(links[2].url(), "https://cdn.test/docs/#intro");
+        assert_eq!(links[2].kind(), LinkKind::Anchor);
+    }
 }
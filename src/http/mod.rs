@@ -0,0 +1,526 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION,
+};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Method, Proxy, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::utils::Result;
+
+pub use transport::{
+    Cassette, Credentials, HttpTransport, MemoryTransport, ReadBody, Reply, Transport,
+};
+
+pub mod transport;
+
+/// User agent sent when none is configured
+pub const DEFAULT_USER_AGENT: &str = concat!("inquest/", env!("CARGO_PKG_VERSION"));
+
+/// Request timeout, in seconds, used when none is configured
+pub const DEFAULT_TIMEOUT: u64 = 30;
+
+/// Redirects followed before a request is abandoned when not specified
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// User agents that can be given by name instead of in full
+pub const USER_AGENT_PRESETS: [(&str, &str); 3] = [
+    (
+        "googlebot-smartphone",
+        "Mozilla/5.0 (Linux; Android 6.0.1; Nexus 5X Build/MMB29P) AppleWebKit/537.36 \
+         (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36 \
+         (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+    ),
+    (
+        "googlebot-desktop",
+        "Mozilla/5.0 AppleWebKit/537.36 (KHTML, like Gecko; compatible; Googlebot/2.1; \
+         +http://www.google.com/bot.html) Chrome/120.0.0.0 Safari/537.36",
+    ),
+    ("bingbot", "Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)"),
+];
+
+// Settings for the client used to fetch remote documents; a plain comment as
+// a doc comment would replace the program's description in `--help`.
+//
+// Headers, cookies and credentials are only sent to the hosts of the probed
+// or crawled urls, or to `auth_hosts` when given, never to linked sites.
+#[derive(StructOpt, Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpOpts {
+    /// User agent sent with every request, or one of the presets googlebot-smartphone,
+    /// googlebot-desktop and bingbot [default: inquest/<version>]
+    #[structopt(long)]
+    pub user_agent: Option<String>,
+    /// Header sent to the probed hosts, as `name: value`; may be repeated
+    #[structopt(long = "header", number_of_values = 1)]
+    pub headers: Vec<String>,
+    /// Cookie sent to the probed hosts, as `name=value`; may be repeated
+    #[structopt(long = "cookie", number_of_values = 1)]
+    pub cookies: Vec<String>,
+    /// Credentials for http basic authentication, as `user:password`
+    #[structopt(long)]
+    pub basic_auth: Option<String>,
+    /// Only host headers, cookies and credentials are sent to; may be repeated
+    /// [default: the hosts of the probed or crawled urls]
+    #[structopt(long = "auth-host", number_of_values = 1)]
+    pub auth_hosts: Vec<String>,
+    /// Proxy every request is sent through
+    #[structopt(long)]
+    pub proxy: Option<String>,
+    /// Seconds to wait for a response before giving up [default: 30]
+    #[structopt(long)]
+    pub timeout: Option<u64>,
+    /// Redirects followed before giving up; 0 reports redirects without following them
+    /// [default: 10]
+    #[structopt(long)]
+    pub max_redirects: Option<usize>,
+    /// Accepts invalid and self signed tls certificates when true; false overrides the
+    /// configuration [default: false]
+    #[structopt(long, possible_values = &["true", "false"])]
+    pub insecure: Option<bool>,
+    /// Records every response to the given cassette file
+    #[structopt(long, conflicts_with = "replay")]
+    pub record: Option<String>,
+    /// Answers every request from the given cassette file instead of the network
+    #[structopt(long)]
+    pub replay: Option<String>,
+}
+
+/// Client remote documents and links are requested with
+///
+/// Follows redirects itself, sending each request through its transport.
+/// Cheap to clone; clones share the same transport.
+#[derive(Clone, Debug)]
+pub struct Client {
+    transport: Arc<dyn Transport>,
+    max_redirects: usize,
+    /// Hosts trusted with the transport's credentials
+    auth_hosts: Arc<RwLock<HashSet<String>>>,
+    /// Whether `auth_hosts` was given up front rather than gathered from targets
+    pinned: bool,
+}
+
+/// Response to a request, along with as much of its body as was read
+pub struct Fetched {
+    pub meta: ResponseMeta,
+    pub body: String,
+}
+
+/// Everything known about the response a document was read from
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ResponseMeta {
+    status: u16,
+    url: String,
+    redirects: Vec<String>,
+    headers: Vec<(String, String)>,
+    content_type: Option<String>,
+    content_length: Option<u64>,
+    ttfb: Duration,
+    total: Duration,
+}
+
+/// Resolves host names with the system resolver, failing with a
+/// `LookupError` so that lookups can be told apart from other connect errors
+struct Resolver;
+
+/// Host name the resolver found no address for
+#[derive(Debug)]
+struct LookupError {
+    host: String,
+    source: std::io::Error,
+}
+
+/// Reason a request could not be completed
+#[derive(Debug)]
+pub enum FetchError {
+    Connection(String),
+    Dns(String),
+    Redirects(usize),
+    Timeout(String),
+    Url(String),
+    Other(String),
+}
+
+// === impl HttpOpts ===
+
+impl HttpOpts {
+    /// Fills any unset option from `defaults`
+    pub fn merge(&mut self, defaults: &HttpOpts) {
+        if self.user_agent.is_none() {
+            self.user_agent = defaults.user_agent.clone();
+        }
+        if self.headers.is_empty() {
+            self.headers = defaults.headers.clone();
+        }
+        if self.cookies.is_empty() {
+            self.cookies = defaults.cookies.clone();
+        }
+        if self.basic_auth.is_none() {
+            self.basic_auth = defaults.basic_auth.clone();
+        }
+        if self.auth_hosts.is_empty() {
+            self.auth_hosts = defaults.auth_hosts.clone();
+        }
+        if self.proxy.is_none() {
+            self.proxy = defaults.proxy.clone();
+        }
+        if self.timeout.is_none() {
+            self.timeout = defaults.timeout;
+        }
+        if self.max_redirects.is_none() {
+            self.max_redirects = defaults.max_redirects;
+        }
+        if self.insecure.is_none() {
+            self.insecure = defaults.insecure;
+        }
+        if self.record.is_none() && self.replay.is_none() {
+            self.record = defaults.record.clone();
+            self.replay = defaults.replay.clone();
+        }
+    }
+
+    /// User agent sent with every request, with presets expanded
+    pub fn user_agent(&self) -> &str {
+        match self.user_agent.as_deref() {
+            Some(agent) => USER_AGENT_PRESETS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(agent))
+                .map_or(agent, |(_, preset)| preset),
+            None => DEFAULT_USER_AGENT,
+        }
+    }
+
+    /// Builds a client honoring these settings
+    ///
+    /// Replaying a cassette never touches the network; recording one sends
+    /// every request through the http transport as usual.
+    pub fn client(&self) -> Result<Client> {
+        let max_redirects = self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS);
+        let client = match (&self.replay, &self.record) {
+            (Some(path), _) => Client::new(Cassette::replay(path)?),
+            (None, Some(path)) => Client::new(Cassette::record(path, self.transport()?)?),
+            (None, None) => Client::new(self.transport()?),
+        };
+        Ok(client.max_redirects(max_redirects).auth_hosts(&self.auth_hosts))
+    }
+
+    fn transport(&self) -> Result<HttpTransport> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(self.user_agent())
+            .timeout(Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT)))
+            .redirect(Policy::none())
+            .danger_accept_invalid_certs(self.insecure.unwrap_or_default())
+            .dns_resolver(Arc::new(Resolver));
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy.as_str()).map_err(|err| {
+                HttpOpts::invalid(format!("proxy {}; {}", proxy, err))
+            })?);
+        }
+        let inner = builder
+            .build()
+            .map_err(|err| Error::new(ErrorType::Message(ErrorKind::Http, err.to_string())))?;
+
+        let basic_auth = self.basic_auth.as_ref().map(|credentials| {
+            match credentials.split_once(':') {
+                Some((user, password)) => (user.to_string(), Some(password.to_string())),
+                None => (credentials.clone(), None),
+            }
+        });
+        Ok(HttpTransport::new(inner, Credentials::new(self.header_map()?, basic_auth)))
+    }
+
+    fn header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for header in &self.headers {
+            let (name, value) = header.split_once(':').ok_or_else(|| {
+                HttpOpts::invalid(format!("header {}; expected `name: value`", header))
+            })?;
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|err| HttpOpts::invalid(format!("header {}; {}", header, err)))?;
+            let value = HeaderValue::from_str(value.trim())
+                .map_err(|err| HttpOpts::invalid(format!("header {}; {}", header, err)))?;
+            headers.append(name, value);
+        }
+        if !self.cookies.is_empty() {
+            let cookies = self.cookies.join("; ");
+            let value = HeaderValue::from_str(&cookies)
+                .map_err(|err| HttpOpts::invalid(format!("cookie {}; {}", cookies, err)))?;
+            headers.insert(COOKIE, value);
+        }
+        Ok(headers)
+    }
+
+    fn invalid(message: String) -> Error {
+        Error::new(ErrorType::Message(ErrorKind::InvalidParameters, message))
+    }
+}
+
+// === impl Client ===
+
+impl Client {
+    /// Sends every request through `transport`
+    pub fn new<T: Transport + 'static>(transport: T) -> Client {
+        Client {
+            transport: Arc::new(transport),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            auth_hosts: Arc::new(RwLock::new(HashSet::new())),
+            pinned: false,
+        }
+    }
+
+    /// Only trusts `hosts` with credentials, whatever is passed to
+    /// `authorize`; an empty slice leaves the targets' hosts trusted
+    pub fn auth_hosts(mut self, hosts: &[String]) -> Self {
+        if !hosts.is_empty() {
+            let hosts = hosts.iter().map(|host| host.trim().to_lowercase()).collect();
+            self.auth_hosts = Arc::new(RwLock::new(hosts));
+            self.pinned = true;
+        }
+        self
+    }
+
+    /// Trusts the host of `url`, a document the user asked for, with
+    /// credentials; clones share the trusted hosts
+    pub fn authorize(&self, url: &str) {
+        if self.pinned {
+            return;
+        }
+        let host = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string));
+        if let Some(host) = host {
+            self.auth_hosts.write().unwrap().insert(host);
+        }
+    }
+
+    fn is_authorized(&self, url: &Url) -> bool {
+        match url.host_str() {
+            Some(host) => self.auth_hosts.read().unwrap().contains(host),
+            None => false,
+        }
+    }
+
+    /// Redirects followed before giving up; with 0 a redirect is returned
+    /// as the response
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Sends a request following as many redirects as allowed, reading the
+    /// final response's body as a document
+    ///
+    /// The response's `redirects` lists the urls that answered with a
+    /// redirect, starting with `url` itself. Credentials go to trusted hosts
+    /// only, and no further once a redirect leaves the requested host.
+    pub async fn fetch(
+        &self,
+        method: Method,
+        url: &str,
+    ) -> std::result::Result<Fetched, FetchError> {
+        self.send(method, url, ReadBody::Document).await
+    }
+
+    /// Same as `fetch`, reading only as much of the body as `body` asks for
+    pub async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        body: ReadBody,
+    ) -> std::result::Result<Fetched, FetchError> {
+        let mut url = Url::parse(url).map_err(|err| FetchError::Url(err.to_string()))?;
+        let mut redirects = Vec::new();
+        let started = Instant::now();
+        let host = url.host_str().map(str::to_string);
+        let trusted = self.is_authorized(&url);
+        loop {
+            let sent = started.elapsed();
+            let credentials = trusted && url.host_str() == host.as_deref();
+            let reply = self.transport.send(&method, &url, credentials, body).await?;
+            let location = reply
+                .get(LOCATION.as_str())
+                .and_then(|location| url.join(location).ok());
+
+            match location {
+                Some(location) if self.max_redirects > 0 && reply.is_redirection() => {
+                    if redirects.len() >= self.max_redirects {
+                        return Err(FetchError::Redirects(redirects.len()));
+                    }
+                    redirects.push(std::mem::replace(&mut url, location));
+                }
+                _ => {
+                    let meta = ResponseMeta::new(&url, &redirects, &reply, sent + reply.ttfb)
+                        .finish(started.elapsed());
+                    return Ok(Fetched { meta, body: reply.body });
+                }
+            }
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        HttpOpts::default().client().unwrap_or_else(|_| {
+            Client::new(HttpTransport::new(reqwest::Client::default(), Credentials::default()))
+        })
+    }
+}
+
+// === impl Resolver ===
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            match tokio::net::lookup_host(format!("{}:0", host)).await {
+                Ok(addrs) => Ok(Box::new(addrs) as Addrs),
+                Err(source) => Err(LookupError { host, source }.into()),
+            }
+        })
+    }
+}
+
+// === impl LookupError ===
+
+impl Display for LookupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no address found for {}; {}", self.host, self.source)
+    }
+}
+
+impl std::error::Error for LookupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+// === impl ResponseMeta ===
+
+impl ResponseMeta {
+    fn new(url: &Url, redirects: &[Url], reply: &Reply, ttfb: Duration) -> ResponseMeta {
+        ResponseMeta {
+            status: reply.status,
+            url: url.to_string(),
+            redirects: redirects.iter().map(Url::to_string).collect(),
+            headers: reply.headers.clone(),
+            content_type: reply.get(CONTENT_TYPE.as_str()).map(str::to_string),
+            content_length: reply
+                .get(CONTENT_LENGTH.as_str())
+                .and_then(|length| length.parse().ok())
+                .or(reply.size),
+            ttfb,
+            total: ttfb,
+        }
+    }
+
+    fn finish(mut self, total: Duration) -> Self {
+        self.total = total;
+        self
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Status of the response as a `StatusCode`
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Url the document was read from, after following any redirect
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Urls that answered with a redirect, starting with the requested one
+    pub fn redirects(&self) -> &[String] {
+        &self.redirects
+    }
+
+    /// Every response header, names lowercased, in the order received
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Values of the named header; `name` is matched case insensitively
+    pub fn header(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Length announced by the server, or the bytes of body read when none was
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Time until the final response's headers arrived
+    pub fn ttfb(&self) -> Duration {
+        self.ttfb
+    }
+
+    /// Time until the body was read in full
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+}
+
+// === impl FetchError ===
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Connection(err) => write!(f, "connection failed; {}", err),
+            FetchError::Dns(err) => write!(f, "dns lookup failed; {}", err),
+            FetchError::Redirects(n) => write!(f, "gave up after {} redirects", n),
+            FetchError::Timeout(err) => write!(f, "timed out; {}", err),
+            FetchError::Url(err) => write!(f, "invalid url; {}", err),
+            FetchError::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(err: reqwest::Error) -> Self {
+        // hyper reports failed lookups as connect errors; only the cause tells them apart
+        let mut dns = false;
+        let mut source = std::error::Error::source(&err);
+        while let Some(cause) = source {
+            dns |= cause.is::<LookupError>();
+            source = cause.source();
+        }
+
+        let message = err.to_string();
+        if err.is_timeout() {
+            FetchError::Timeout(message)
+        } else if dns {
+            FetchError::Dns(message)
+        } else if err.is_connect() {
+            FetchError::Connection(message)
+        } else {
+            FetchError::Other(message)
+        }
+    }
+}
+
+impl From<FetchError> for Error {
+    fn from(err: FetchError) -> Self {
+        let kind = match err {
+            FetchError::Url(_) => ErrorKind::InvalidUrl,
+            _ => ErrorKind::Http,
+        };
+        Error::new(ErrorType::Message(kind, err.to_string()))
+    }
+}
@@ -0,0 +1,583 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::http::FetchError;
+use crate::utils::Result;
+
+/// Sends a single request, without following redirects
+///
+/// `Client` is built on top of a transport; swapping it lets documents be
+/// served from memory or from a recording instead of the network.
+#[async_trait]
+pub trait Transport: Debug + Send + Sync {
+    /// `credentials` tells whether the configured headers, cookies and
+    /// credentials may be sent to `url`; `body` how much of the response's
+    /// body is read, which is nothing in answer to `HEAD`
+    async fn send(
+        &self,
+        method: &Method,
+        url: &Url,
+        credentials: bool,
+        body: ReadBody,
+    ) -> std::result::Result<Reply, FetchError>;
+}
+
+/// How much of a response's body a transport reads
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadBody {
+    /// Reads the body and decodes it as text, for documents
+    Document,
+    /// Counts the body's bytes without keeping them, for images and the like
+    Size,
+    /// Leaves the body unread; only the status and headers are wanted
+    Nothing,
+}
+
+/// Response to a single request
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Reply {
+    pub status: u16,
+    /// Header names are lowercase, in the order received
+    pub headers: Vec<(String, String)>,
+    /// Decoded body; empty unless read as a document
+    pub body: String,
+    /// Bytes of body received, before decoding; none when it was not read
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Time until the headers arrived
+    #[serde(skip)]
+    pub ttfb: Duration,
+}
+
+/// Headers, cookies and basic authentication only sent to trusted hosts
+#[derive(Clone, Debug, Default)]
+pub struct Credentials {
+    headers: HeaderMap,
+    basic_auth: Option<(String, Option<String>)>,
+}
+
+/// Sends requests over the network
+#[derive(Debug)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    credentials: Credentials,
+}
+
+/// Answers requests from canned replies, keyed by url
+///
+/// Requests for any other url fail as if the host could not be reached.
+#[derive(Debug, Default)]
+pub struct MemoryTransport {
+    replies: HashMap<String, Reply>,
+}
+
+/// Replies recorded to, or replayed from, a file on disk
+///
+/// The file holds one json entry per line. Recording sends every request
+/// through another transport and appends each reply as it arrives;
+/// replaying answers from the file alone, with the latest reply to a request.
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    recorder: Option<(Box<dyn Transport>, Mutex<tokio::fs::File>)>,
+    entries: Vec<Entry>,
+}
+
+/// A recorded request and the reply it got
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Entry {
+    method: String,
+    url: String,
+    #[serde(flatten)]
+    reply: Reply,
+}
+
+// === impl Reply ===
+
+impl Reply {
+    pub fn new(status: u16) -> Reply {
+        Reply {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+            size: None,
+            ttfb: Duration::default(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self.size = Some(body.len() as u64);
+        self
+    }
+
+    /// The reply as it would have been read with `body`
+    fn read(mut self, method: &Method, body: ReadBody) -> Self {
+        match (method, body) {
+            (&Method::HEAD, _) | (_, ReadBody::Nothing) => {
+                self.body = String::new();
+                self.size = None;
+            }
+            (_, ReadBody::Size) => self.body = String::new(),
+            (_, ReadBody::Document) => {}
+        }
+        self
+    }
+
+    /// First value of the named header; `name` must be lowercase
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.status)
+    }
+}
+
+// === impl Credentials ===
+
+impl Credentials {
+    /// `headers` include any cookie; `basic_auth` is a user and password
+    pub fn new(headers: HeaderMap, basic_auth: Option<(String, Option<String>)>) -> Self {
+        Credentials { headers, basic_auth }
+    }
+}
+
+// === impl HttpTransport ===
+
+impl HttpTransport {
+    /// Sends requests with `client`, adding `credentials` to those allowed them
+    pub fn new(client: reqwest::Client, credentials: Credentials) -> Self {
+        HttpTransport { client, credentials }
+    }
+
+    /// Charset named by the response's content type, utf-8 when none is
+    fn encoding(response: &reqwest::Response) -> &'static Encoding {
+        response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| {
+                value.split(';').find_map(|param| {
+                    let (name, charset) = param.split_once('=')?;
+                    name.trim().eq_ignore_ascii_case("charset").then_some(charset)
+                })
+            })
+            .and_then(|charset| Encoding::for_label(charset.trim().trim_matches('"').as_bytes()))
+            .unwrap_or(UTF_8)
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn send(
+        &self,
+        method: &Method,
+        url: &Url,
+        credentials: bool,
+        body: ReadBody,
+    ) -> std::result::Result<Reply, FetchError> {
+        let started = Instant::now();
+        let mut request = self.client.request(method.clone(), url.clone());
+        if credentials {
+            request = request.headers(self.credentials.headers.clone());
+            if let Some((user, password)) = &self.credentials.basic_auth {
+                request = request.basic_auth(user, password.as_ref());
+            }
+        }
+        let mut response = request.send().await?;
+        let ttfb = started.elapsed();
+
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned())
+            })
+            .collect();
+        let (body, size) = match (method, body) {
+            (&Method::HEAD, _) | (_, ReadBody::Nothing) => (String::new(), None),
+            (_, ReadBody::Size) => {
+                let mut size = 0;
+                while let Some(chunk) = response.chunk().await? {
+                    size += chunk.len() as u64;
+                }
+                (String::new(), Some(size))
+            }
+            (_, ReadBody::Document) => {
+                let encoding = HttpTransport::encoding(&response);
+                let bytes = response.bytes().await?;
+                let (text, _, _) = encoding.decode(&bytes);
+                (text.into_owned(), Some(bytes.len() as u64))
+            }
+        };
+        Ok(Reply { status, headers, body, size, ttfb })
+    }
+}
+
+// === impl MemoryTransport ===
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        MemoryTransport::default()
+    }
+
+    /// Answers requests for `url` with `reply`
+    pub fn reply(mut self, url: &str, reply: Reply) -> Self {
+        self.replies.insert(url.to_string(), reply);
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn send(
+        &self,
+        method: &Method,
+        url: &Url,
+        _credentials: bool,
+        body: ReadBody,
+    ) -> std::result::Result<Reply, FetchError> {
+        match self.replies.get(url.as_str()) {
+            Some(reply) => Ok(reply.clone().read(method, body)),
+            None => Err(FetchError::Connection(format!("no reply for {}", url))),
+        }
+    }
+}
+
+// === impl Cassette ===
+
+impl Cassette {
+    /// Records every reply `transport` gets to `path`, keeping whatever was
+    /// recorded there before
+    pub fn record<T: Transport + 'static>(path: &str, transport: T) -> Result<Cassette> {
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path).map_err(|err| {
+            Error::new(ErrorType::Message(ErrorKind::Create, format!("{}; {}", path, err)))
+        })?;
+        Ok(Cassette {
+            path: PathBuf::from(path),
+            recorder: Some((Box::new(transport), Mutex::new(tokio::fs::File::from_std(file)))),
+            entries: Vec::new(),
+        })
+    }
+
+    /// Answers every request from the replies recorded at `path`
+    pub fn replay(path: &str) -> Result<Cassette> {
+        Ok(Cassette {
+            path: PathBuf::from(path),
+            recorder: None,
+            entries: Cassette::load(path)?,
+        })
+    }
+
+    fn load(path: &str) -> Result<Vec<Entry>> {
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            Error::new(ErrorType::Message(ErrorKind::FileNotFound, format!("{}; {}", path, err)))
+        })?;
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|err| {
+                    Error::new(ErrorType::Message(ErrorKind::Parse, format!("{}; {}", path, err)))
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Transport for Cassette {
+    async fn send(
+        &self,
+        method: &Method,
+        url: &Url,
+        credentials: bool,
+        body: ReadBody,
+    ) -> std::result::Result<Reply, FetchError> {
+        let (recorder, file) = match &self.recorder {
+            Some(recorder) => recorder,
+            None => {
+                return self
+                    .entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.method == method.as_str() && entry.url == url.as_str())
+                    .map(|entry| entry.reply.clone().read(method, body))
+                    .ok_or_else(|| {
+                        FetchError::Connection(format!("{} {} was not recorded", method, url))
+                    });
+            }
+        };
+
+        let reply = recorder.send(method, url, credentials, body).await?;
+        let entry = Entry {
+            method: method.to_string(),
+            url: url.to_string(),
+            reply: reply.clone(),
+        };
+        let mut line =
+            serde_json::to_string(&entry).map_err(|err| FetchError::Other(err.to_string()))?;
+        line.push('\n');
+
+        // a whole line is written at once so concurrent replies never interleave
+        let mut file = file.lock().await;
+        let written = match file.write_all(line.as_bytes()).await {
+            Ok(()) => file.flush().await,
+            Err(err) => Err(err),
+        };
+        written.map_err(|err| FetchError::Other(format!("{}; {}", self.path.display(), err)))?;
+        Ok(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::http::Client;
+
+    /// Answers from memory, remembering whether each request carried credentials
+    #[derive(Debug, Default)]
+    struct Spy {
+        replies: MemoryTransport,
+        sent: Arc<std::sync::Mutex<Vec<(String, bool)>>>,
+    }
+
+    #[async_trait]
+    impl Transport for Spy {
+        async fn send(
+            &self,
+            method: &Method,
+            url: &Url,
+            credentials: bool,
+            body: ReadBody,
+        ) -> std::result::Result<Reply, FetchError> {
+            self.sent.lock().unwrap().push((url.to_string(), credentials));
+            self.replies.send(method, url, credentials, body).await
+        }
+    }
+
+    fn page(body: &str) -> Reply {
+        Reply::new(200).header("Content-Type", "text/html").body(body)
+    }
+
+    fn redirect(location: &str) -> Reply {
+        Reply::new(301).header("Location", location)
+    }
+
+    fn cassette_path(name: &str) -> String {
+        let name = format!("inquest-{}-{}.ndjson", name, std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    #[tokio::test]
+    async fn memory_transport_reads_only_the_body_asked_for() {
+        let transport = MemoryTransport::new().reply("http://a.test/", page("<p>hi</p>"));
+        let url = Url::parse("http://a.test/").unwrap();
+
+        let document = transport.send(&Method::GET, &url, false, ReadBody::Document).await.unwrap();
+        assert_eq!(document.body, "<p>hi</p>");
+        assert_eq!(document.size, Some(9));
+
+        let size = transport.send(&Method::GET, &url, false, ReadBody::Size).await.unwrap();
+        assert_eq!(size.body, "");
+        assert_eq!(size.size, Some(9));
+
+        let head = transport.send(&Method::HEAD, &url, false, ReadBody::Document).await.unwrap();
+        assert_eq!(head.body, "");
+        assert_eq!(head.size, None);
+
+        let missing = Url::parse("http://b.test/").unwrap();
+        assert!(transport.send(&Method::GET, &missing, false, ReadBody::Document).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn client_follows_redirects() {
+        let transport = MemoryTransport::new()
+            .reply("http://a.test/old", redirect("/new"))
+            .reply("http://a.test/new", page("moved"));
+        let fetched = Client::new(transport).fetch(Method::GET, "http://a.test/old").await.unwrap();
+
+        assert_eq!(fetched.meta.status(), 200);
+        assert_eq!(fetched.meta.url(), "http://a.test/new");
+        assert_eq!(fetched.meta.redirects(), ["http://a.test/old"]);
+        assert_eq!(fetched.meta.content_length(), Some(5));
+        assert_eq!(fetched.body, "moved");
+    }
+
+    #[tokio::test]
+    async fn client_gives_up_after_max_redirects() {
+        let transport = MemoryTransport::new()
+            .reply("http://a.test/1", redirect("/2"))
+            .reply("http://a.test/2", redirect("/1"));
+        let client = Client::new(transport).max_redirects(3);
+
+        let err = client.fetch(Method::GET, "http://a.test/1").await.err().unwrap();
+        assert!(matches!(err, FetchError::Redirects(3)));
+    }
+
+    #[tokio::test]
+    async fn credentials_only_go_to_authorized_hosts() {
+        let spy = Spy {
+            replies: MemoryTransport::new()
+                .reply("http://site.test/", page("home"))
+                .reply("http://other.test/", page("elsewhere"))
+                .reply("http://site.test/away", redirect("http://other.test/"))
+                .reply("http://other.test/back", redirect("http://site.test/")),
+            ..Spy::default()
+        };
+        let sent = spy.sent.clone();
+        let client = Client::new(spy);
+        client.authorize("http://site.test/page");
+
+        for url in ["http://site.test/", "http://other.test/", "http://site.test/away"] {
+            client.fetch(Method::GET, url).await.unwrap();
+        }
+        // only trusted when the request starts on an authorized host
+        client.fetch(Method::GET, "http://other.test/back").await.unwrap();
+
+        let sent = sent.lock().unwrap().clone();
+        let expected = [
+            ("http://site.test/", true),
+            ("http://other.test/", false),
+            ("http://site.test/away", true),
+            ("http://other.test/", false),
+            ("http://other.test/back", false),
+            ("http://site.test/", false),
+        ];
+        let expected = expected.iter().map(|(url, c)| (url.to_string(), *c)).collect::<Vec<_>>();
+        assert_eq!(sent, expected);
+    }
+
+    #[tokio::test]
+    async fn pinned_auth_hosts_ignore_targets() {
+        let spy = Spy {
+            replies: MemoryTransport::new()
+                .reply("http://site.test/", page("home"))
+                .reply("http://api.test/", page("api")),
+            ..Spy::default()
+        };
+        let sent = spy.sent.clone();
+        let client = Client::new(spy).auth_hosts(&["API.test".to_string()]);
+        client.authorize("http://site.test/");
+
+        client.fetch(Method::GET, "http://site.test/").await.unwrap();
+        client.fetch(Method::GET, "http://api.test/").await.unwrap();
+
+        let sent = sent.lock().unwrap().clone();
+        assert_eq!(sent, [("http://site.test/".into(), false), ("http://api.test/".into(), true)]);
+    }
+
+    /// Serves `response` once to each connection on a local port
+    async fn serve(response: &'static [u8]) -> Url {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket.write_all(response).await;
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn http_transport_counts_binary_bodies_without_decoding_them() {
+        let url = serve(
+            b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nConnection: close\r\n\r\n\
+              \x89PNG\xff\xfe\x00\x01",
+        )
+        .await;
+        let transport = HttpTransport::new(reqwest::Client::new(), Credentials::default());
+
+        let reply = transport.send(&Method::GET, &url, false, ReadBody::Size).await.unwrap();
+        assert_eq!(reply.size, Some(8));
+        assert_eq!(reply.body, "");
+        let reply = transport.send(&Method::GET, &url, false, ReadBody::Nothing).await.unwrap();
+        assert_eq!((reply.status, reply.size), (200, None));
+    }
+
+    #[tokio::test]
+    async fn http_transport_decodes_documents_with_their_charset() {
+        let url = serve(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=ISO-8859-1\r\n\
+              Connection: close\r\n\r\ncaf\xe9",
+        )
+        .await;
+        let transport = HttpTransport::new(reqwest::Client::new(), Credentials::default());
+
+        let reply = transport.send(&Method::GET, &url, false, ReadBody::Document).await.unwrap();
+        assert_eq!(reply.body, "caf\u{e9}");
+        assert_eq!(reply.size, Some(4));
+    }
+
+    #[tokio::test]
+    async fn cassette_replays_what_was_recorded() {
+        let path = cassette_path("replay");
+        let transport = MemoryTransport::new()
+            .reply("http://a.test/", page("first"))
+            .reply(
+                "http://a.test/img.png",
+                Reply::new(200).header("Content-Type", "image/png").body("PNG"),
+            );
+        let recorder = Client::new(Cassette::record(&path, transport).unwrap());
+        recorder.fetch(Method::GET, "http://a.test/").await.unwrap();
+        recorder.send(Method::GET, "http://a.test/img.png", ReadBody::Size).await.unwrap();
+
+        let replay = Client::new(Cassette::replay(&path).unwrap());
+        let fetched = replay.fetch(Method::GET, "http://a.test/").await.unwrap();
+        assert_eq!(fetched.body, "first");
+        let image = replay.send(Method::GET, "http://a.test/img.png", ReadBody::Size).await;
+        let image = image.unwrap();
+        assert_eq!(image.meta.content_length(), Some(3));
+        assert_eq!(image.body, "");
+        assert!(replay.fetch(Method::HEAD, "http://a.test/").await.is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn cassette_appends_and_replays_the_latest_reply() {
+        let path = cassette_path("append");
+        let first = MemoryTransport::new().reply("http://a.test/", page("old"));
+        Client::new(Cassette::record(&path, first).unwrap())
+            .fetch(Method::GET, "http://a.test/")
+            .await
+            .unwrap();
+        let second = MemoryTransport::new().reply("http://a.test/", page("new"));
+        let recorder = Client::new(Cassette::record(&path, second).unwrap());
+        let requests = (0..8).map(|_| recorder.fetch(Method::GET, "http://a.test/"));
+        for fetched in futures::future::join_all(requests).await {
+            fetched.unwrap();
+        }
+
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, 9);
+        let replay = Client::new(Cassette::replay(&path).unwrap());
+        assert_eq!(replay.fetch(Method::GET, "http://a.test/").await.unwrap().body, "new");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,395 @@
+use futures::{stream, StreamExt};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::audit::{Finding, Severity};
+use crate::html::{HtmlParser, Image};
+use crate::http::{self, FetchError, Fetched, ReadBody};
+use crate::logging::CLI;
+use crate::utils::Result;
+
+/// Images requested at once when fetching
+pub const FETCH_CONCURRENCY: usize = 4;
+
+/// Formats with a better compressed modern alternative, by file extension
+pub const LEGACY_FORMATS: [&str; 7] = ["bmp", "gif", "jpeg", "jpg", "png", "tif", "tiff"];
+
+/// What fetching a single image turned up
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ImageFile {
+    url: String,
+    status: Option<u16>,
+    bytes: Option<u64>,
+    content_type: Option<String>,
+    error: Option<String>,
+}
+
+/// Images of a document along with the problems found in them
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ImageReport {
+    images: Vec<Image>,
+    /// One per distinct url, once fetched
+    files: Vec<ImageFile>,
+    findings: Vec<Finding>,
+}
+
+// === impl ImageFile ===
+
+impl ImageFile {
+    fn new(url: &str) -> ImageFile {
+        ImageFile {
+            url: url.to_string(),
+            status: None,
+            bytes: None,
+            content_type: None,
+            error: None,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Status of the final response; files read from disk have none
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// Size announced by the server, or counted from the body it sent, or of
+    /// the file on disk
+    pub fn bytes(&self) -> Option<u64> {
+        self.bytes
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Why the image could not be fetched
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.error.is_some() || self.status.is_some_and(|s| s >= 400)
+    }
+
+    async fn fetch(client: http::Client, url: String) -> ImageFile {
+        let mut file = ImageFile::new(&url);
+        let parsed = match Url::parse(&url) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                file.error = Some(err.to_string());
+                return file;
+            }
+        };
+        match parsed.scheme() {
+            "http" | "https" => {}
+            "file" => {
+                let metadata = match parsed.to_file_path() {
+                    Ok(path) => tokio::fs::metadata(&path).await.map_err(|err| err.to_string()),
+                    Err(_) => Err("invalid file path".to_string()),
+                };
+                match metadata {
+                    Ok(metadata) => file.bytes = Some(metadata.len()),
+                    Err(err) => file.error = Some(err),
+                }
+                return file;
+            }
+            // data: urls and the like are part of the document already
+            _ => return file,
+        }
+
+        event!(target: CLI, Level::DEBUG, "fetching image; url={}", url);
+        // the size is usually announced in answer to HEAD; anything short of
+        // that is counted from the body of a GET, which is never kept
+        let head = client.send(Method::HEAD, &url, ReadBody::Nothing).await;
+        let fetched = match head {
+            Ok(fetched)
+                if fetched.meta.status_code().is_success()
+                    && fetched.meta.content_length().is_some() =>
+            {
+                Ok(fetched)
+            }
+            Err(FetchError::Timeout(err)) => Err(FetchError::Timeout(err)),
+            _ => client.send(Method::GET, &url, ReadBody::Size).await,
+        };
+        match fetched {
+            // the body of an error status is a page about the error, not the image
+            Ok(Fetched { meta, .. }) if !meta.status_code().is_success() => {
+                file.status = Some(meta.status());
+            }
+            Ok(Fetched { meta, .. }) => {
+                file.status = Some(meta.status());
+                file.bytes = meta.content_length();
+                file.content_type = meta.content_type().map(str::to_string);
+            }
+            Err(err) => file.error = Some(err.to_string()),
+        }
+        file
+    }
+}
+
+// === impl ImageReport ===
+
+impl ImageReport {
+    /// Lists the document's images and checks them for missing alt text,
+    /// missing dimensions and legacy formats
+    pub fn collect<P: HtmlParser>(parser: &P) -> Result<ImageReport> {
+        let mut report = ImageReport {
+            images: parser.images()?,
+            files: Vec::new(),
+            findings: Vec::new(),
+        };
+        report.check();
+        Ok(report)
+    }
+
+    /// Requests every distinct image url with `client`, recording its size
+    /// and content type, and checks the images again with what was learned
+    pub async fn fetch(&mut self, client: &http::Client) {
+        let mut urls: Vec<&str> = Vec::new();
+        for url in self.images.iter().filter_map(Image::url) {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+        let fetches = urls
+            .into_iter()
+            .map(|url| ImageFile::fetch(client.clone(), url.to_string()))
+            .collect::<Vec<_>>();
+        self.files = stream::iter(fetches).buffered(FETCH_CONCURRENCY).collect().await;
+        self.check();
+    }
+
+    pub fn images(&self) -> &[Image] {
+        &self.images
+    }
+
+    /// What fetching the image's url turned up, if it was fetched
+    pub fn file(&self, image: &Image) -> Option<&ImageFile> {
+        let url = image.url()?;
+        self.files.iter().find(|file| file.url() == url)
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    fn check(&mut self) {
+        let mut findings = Vec::new();
+        // sources come before the img of their picture
+        let mut sources = false;
+        for image in &self.images {
+            let name = image.url().or_else(|| image.src()).unwrap_or("image without a src");
+            let file = self.file(image);
+            if let Some(file) = file.filter(|file| file.is_broken()) {
+                let reason = match (file.error(), file.status()) {
+                    (Some(error), _) => error.to_string(),
+                    (None, Some(status)) => format!("status {}", status),
+                    (None, None) => String::new(),
+                };
+                findings.push(Finding::new(
+                    "broken-image",
+                    Severity::Error,
+                    format!("{} could not be fetched; {}", name, reason),
+                ));
+            }
+
+            let fallback = image.in_picture() && sources;
+            sources = image.tag() != "img";
+            let format = file
+                .and_then(ImageFile::content_type)
+                .or_else(|| image.media_type())
+                .and_then(ImageReport::format_of_type)
+                .or_else(|| image.url().and_then(ImageReport::format_of_url));
+            // the fallback of a picture is only shown by browsers without
+            // support for its sources
+            if let Some(format) = format.filter(|f| LEGACY_FORMATS.contains(f) && !fallback) {
+                findings.push(Finding::new(
+                    "legacy-format",
+                    Severity::Info,
+                    format!("{} is a {}; webp or avif are usually smaller", name, format),
+                ));
+            }
+            if image.tag() != "img" {
+                continue;
+            }
+
+            if image.url().is_none() {
+                findings.push(Finding::new(
+                    "missing-src",
+                    Severity::Error,
+                    "img has neither a src nor a srcset".to_string(),
+                ));
+            }
+            match image.alt() {
+                None => findings.push(Finding::new(
+                    "missing-alt",
+                    Severity::Error,
+                    format!("{} has no alt attribute", name),
+                )),
+                Some(alt) if alt.trim().is_empty() => findings.push(Finding::new(
+                    "empty-alt",
+                    Severity::Info,
+                    format!("{} has an empty alt and is ignored by screen readers", name),
+                )),
+                Some(_) => {}
+            }
+            // without them the browser cannot reserve space, and the layout
+            // shifts once the image loads
+            if image.width().is_none() || image.height().is_none() {
+                findings.push(Finding::new(
+                    "missing-dimensions",
+                    Severity::Warning,
+                    format!("{} has no width or height", name),
+                ));
+            }
+        }
+        self.findings = findings;
+    }
+
+    /// Format named by a content type such as `image/png`
+    fn format_of_type(content_type: &str) -> Option<&str> {
+        let subtype = content_type.split(';').next()?.trim().strip_prefix("image/")?;
+        Some(subtype.strip_suffix("+xml").unwrap_or(subtype))
+    }
+
+    /// Format named by the extension of the url's path
+    fn format_of_url(url: &str) -> Option<&'static str> {
+        let url = Url::parse(url).ok()?;
+        let (_, extension) = url.path().rsplit_once('.')?;
+        let extension = extension.to_lowercase();
+        LEGACY_FORMATS.iter().find(|format| **format == extension).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::Html;
+    use crate::http::{Client, MemoryTransport, Reply};
+
+    fn collect(html: &str) -> ImageReport {
+        ImageReport::collect(&Html::inline(html, Some("http://site.test/"))).unwrap()
+    }
+
+    fn findings(report: &ImageReport) -> Vec<(&str, Severity, &str)> {
+        report.findings().iter().map(|f| (f.rule(), f.severity(), f.message())).collect()
+    }
+
+    #[test]
+    fn images_need_alt_text_unless_decorative() {
+        let report = collect(
+            "<img src=cat.webp alt='A cat' width=1 height=1>
+             <img src=dog.webp width=1 height=1>
+             <img src=line.webp alt=' ' width=1 height=1>",
+        );
+
+        assert_eq!(
+            findings(&report),
+            [
+                ("missing-alt", Severity::Error, "http://site.test/dog.webp has no alt attribute"),
+                (
+                    "empty-alt",
+                    Severity::Info,
+                    "http://site.test/line.webp has an empty alt and is ignored by screen readers"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn images_need_a_source_and_both_dimensions() {
+        let report = collect("<img src=a.webp alt=a width=10><img alt=b width=1 height=1>");
+
+        assert_eq!(
+            findings(&report),
+            [
+                (
+                    "missing-dimensions",
+                    Severity::Warning,
+                    "http://site.test/a.webp has no width or height"
+                ),
+                ("missing-src", Severity::Error, "img has neither a src nor a srcset"),
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_formats_are_reported_unless_a_picture_fallback() {
+        let report = collect(
+            "<picture>
+               <source srcset=a.avif type=image/avif>
+               <img src=a.png alt=a width=1 height=1>
+             </picture>
+             <img src=b.GIF alt=b width=1 height=1>",
+        );
+
+        assert_eq!(
+            findings(&report),
+            [(
+                "legacy-format",
+                Severity::Info,
+                "http://site.test/b.GIF is a gif; webp or avif are usually smaller"
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetching_finds_broken_images_and_their_actual_format() {
+        let transport = MemoryTransport::new()
+            .reply(
+                "http://site.test/photo.webp",
+                Reply::new(200).header("Content-Type", "image/png").body(&"x".repeat(2048)),
+            )
+            .reply("http://site.test/gone.webp", Reply::new(404));
+        let mut report = collect(
+            "<img src=photo.webp alt=a width=1 height=1>
+             <img src=gone.webp alt=b width=1 height=1>
+             <img src=photo.webp alt=c width=1 height=1>
+             <img src=offline.webp alt=d width=1 height=1>",
+        );
+        assert!(report.findings().is_empty());
+
+        report.fetch(&Client::new(transport)).await;
+
+        // a url used by several images is only fetched once
+        let photo = report.file(&report.images()[0]).unwrap();
+        assert_eq!(photo.status(), Some(200));
+        assert_eq!(photo.bytes(), Some(2048));
+        assert_eq!(photo.content_type(), Some("image/png"));
+        assert!(!photo.is_broken());
+        assert_eq!(report.files.len(), 3);
+
+        let offline = report.file(&report.images()[3]).unwrap();
+        let error = offline.error().unwrap();
+        assert!(error.contains("no reply for http://site.test/offline.webp"));
+        assert_eq!(offline.status(), None);
+        assert!(offline.is_broken());
+        let unreachable = format!("http://site.test/offline.webp could not be fetched; {}", error);
+
+        assert_eq!(
+            findings(&report),
+            [
+                (
+                    "legacy-format",
+                    Severity::Info,
+                    "http://site.test/photo.webp is a png; webp or avif are usually smaller"
+                ),
+                (
+                    "broken-image",
+                    Severity::Error,
+                    "http://site.test/gone.webp could not be fetched; status 404"
+                ),
+                (
+                    "legacy-format",
+                    Severity::Info,
+                    "http://site.test/photo.webp is a png; webp or avif are usually smaller"
+                ),
+                ("broken-image", Severity::Error, &unreachable),
+            ]
+        );
+    }
+}
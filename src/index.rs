@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::html::{HtmlParser, RobotsMeta};
+use crate::http::{self, Fetched, ResponseMeta};
+use crate::logging::CLI;
+use crate::utils::Result;
+
+/// Crawler the verdict is given for when not specified
+pub const DEFAULT_BOT: &str = "googlebot";
+
+/// Whether a page can make it into a search index
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Indexable,
+    /// A robots meta tag or `X-Robots-Tag` header asks for `noindex`
+    Noindex,
+    /// The canonical url points to another page
+    Canonicalized,
+    /// `robots.txt` disallows crawling the page
+    Blocked,
+    /// The server answered with a redirect or error status
+    Unavailable,
+}
+
+/// Indexability of a single page, along with the signals it was derived from
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Indexability {
+    verdict: Verdict,
+    reason: Option<String>,
+    canonicals: Vec<String>,
+    robots: Vec<RobotsMeta>,
+    x_robots_tag: Vec<RobotsMeta>,
+}
+
+/// Parsed `robots.txt`
+#[derive(Clone, Debug, Default)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+}
+
+/// Rules given to one or more user agents
+#[derive(Clone, Debug, Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+/// Pending or completed fetch of a single `robots.txt`
+type Fetch = Shared<BoxFuture<'static, Arc<RobotsTxt>>>;
+
+/// Fetches each origin's `robots.txt` only once however many pages ask for it
+///
+/// Clones share the same files.
+#[derive(Clone)]
+pub struct RobotsCache {
+    client: http::Client,
+    fetched: Arc<Mutex<HashMap<String, Fetch>>>,
+}
+
+// === impl Verdict ===
+
+impl Verdict {
+    pub fn is_indexable(&self) -> bool {
+        *self == Verdict::Indexable
+    }
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Indexable => write!(f, "indexable"),
+            Verdict::Noindex => write!(f, "noindex"),
+            Verdict::Canonicalized => write!(f, "canonicalized"),
+            Verdict::Blocked => write!(f, "blocked"),
+            Verdict::Unavailable => write!(f, "unavailable"),
+        }
+    }
+}
+
+// === impl Indexability ===
+
+impl Indexability {
+    /// Gives the verdict for `bot`, from the most to the least definitive signal
+    ///
+    /// `blocked` is the `robots.txt` rule disallowing the page, if any. Without
+    /// a `response` the page's status and location are unknown, so neither
+    /// the status nor the canonical url can rule it out.
+    pub fn assess<P: HtmlParser>(
+        parser: &P,
+        response: Option<&ResponseMeta>,
+        blocked: Option<&str>,
+        bot: &str,
+    ) -> Result<Indexability> {
+        let canonicals = parser.canonicals()?;
+        let robots = parser.robots()?;
+        let x_robots_tag: Vec<RobotsMeta> = response
+            .map(|r| r.header("x-robots-tag").into_iter().map(RobotsMeta::from_header).collect())
+            .unwrap_or_default();
+
+        let noindex = robots
+            .iter()
+            .find(|meta| meta.applies_to(bot) && meta.is_noindex())
+            .map(|meta| {
+                let name = meta.agent().unwrap_or("robots");
+                format!("<meta name=\"{}\"> {}", name, meta.directives().join(", "))
+            })
+            .or_else(|| {
+                x_robots_tag
+                    .iter()
+                    .find(|meta| meta.applies_to(bot) && meta.is_noindex())
+                    .map(|meta| format!("x-robots-tag {}", meta))
+            });
+        let elsewhere = response
+            .and_then(|r| Url::parse(r.url()).ok())
+            .and_then(|page| Indexability::canonical(&canonicals, &page));
+
+        let (verdict, reason) = if let Some(rule) = blocked {
+            (Verdict::Blocked, Some(format!("robots.txt disallows {}", rule)))
+        } else if let Some(r) = response.filter(|r| !r.status_code().is_success()) {
+            (Verdict::Unavailable, Some(format!("status {}", r.status())))
+        } else if let Some(noindex) = noindex {
+            (Verdict::Noindex, Some(noindex))
+        } else if let Some(canonical) = elsewhere {
+            (Verdict::Canonicalized, Some(format!("canonical is {}", canonical)))
+        } else {
+            (Verdict::Indexable, None)
+        };
+
+        Ok(Indexability {
+            verdict,
+            reason,
+            canonicals,
+            robots,
+            x_robots_tag,
+        })
+    }
+
+    /// Verdict for a page that was never fetched, as the robots.txt `rule`
+    /// disallows it
+    pub fn blocked(rule: &str) -> Indexability {
+        Indexability {
+            verdict: Verdict::Blocked,
+            reason: Some(format!("robots.txt disallows {}", rule)),
+            canonicals: Vec::new(),
+            robots: Vec::new(),
+            x_robots_tag: Vec::new(),
+        }
+    }
+
+    pub fn verdict(&self) -> Verdict {
+        self.verdict
+    }
+
+    /// Signal behind any verdict other than `indexable`
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Every canonical url declared, resolved against the document's base url
+    pub fn canonicals(&self) -> &[String] {
+        &self.canonicals
+    }
+
+    /// Every robots meta tag, including those given to other crawlers
+    pub fn robots(&self) -> &[RobotsMeta] {
+        &self.robots
+    }
+
+    /// Every `X-Robots-Tag` header, including those given to other crawlers
+    pub fn x_robots_tag(&self) -> &[RobotsMeta] {
+        &self.x_robots_tag
+    }
+
+    /// The canonical url when it points away from `page`; conflicting
+    /// canonicals are ignored, as search engines do
+    fn canonical<'a>(canonicals: &'a [String], page: &Url) -> Option<&'a str> {
+        let first = canonicals.first()?;
+        if canonicals.iter().any(|c| c != first) {
+            return None;
+        }
+        let mut canonical = Url::parse(first).ok()?;
+        let mut page = page.clone();
+        canonical.set_fragment(None);
+        page.set_fragment(None);
+        (canonical != page).then_some(first.as_str())
+    }
+}
+
+// === impl RobotsTxt ===
+
+impl RobotsTxt {
+    /// Parses the groups of `user-agent`, `allow` and `disallow` lines;
+    /// anything else is ignored
+    pub fn parse(text: &str) -> RobotsTxt {
+        let mut groups: Vec<Group> = Vec::new();
+        // consecutive user-agent lines share the rules that follow them
+        let mut open = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field.trim().to_lowercase(), value.trim()),
+                None => continue,
+            };
+            match field.as_str() {
+                "user-agent" => {
+                    if !open {
+                        groups.push(Group::default());
+                        open = true;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(value.to_lowercase());
+                    }
+                }
+                "allow" | "disallow" => {
+                    open = false;
+                    // an empty disallow allows everything, which is the default
+                    if let (Some(group), false) = (groups.last_mut(), value.is_empty()) {
+                        group.rules.push(Rule {
+                            allow: field == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                _ => open = false,
+            }
+        }
+        RobotsTxt { groups }
+    }
+
+    /// The `disallow` rule keeping `bot` away from `url`, if any
+    ///
+    /// Only the groups for the most specific agent matching `bot` apply,
+    /// falling back to `*`; `googlebot` rules also apply to `googlebot-news`.
+    /// The longest matching rule wins, and `allow` wins a tie.
+    pub fn disallowed_by(&self, bot: &str, url: &Url) -> Option<&str> {
+        let bot = bot.to_lowercase();
+        let agent = self
+            .groups
+            .iter()
+            .flat_map(|group| &group.agents)
+            .filter(|agent| match bot.strip_prefix(agent.as_str()) {
+                Some(rest) => rest.is_empty() || rest.starts_with('-'),
+                None => false,
+            })
+            .max_by_key(|agent| agent.len())
+            .map(String::as_str)
+            .unwrap_or("*");
+
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let rule = self
+            .groups
+            .iter()
+            .filter(|group| group.agents.iter().any(|a| a == agent))
+            .flat_map(|group| &group.rules)
+            .filter(|rule| Rule::matches(&rule.pattern, &path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))?;
+        (!rule.allow).then_some(rule.pattern.as_str())
+    }
+}
+
+// === impl Rule ===
+
+impl Rule {
+    /// Matches `path` against a pattern where `*` stands for any sequence of
+    /// characters and a trailing `$` anchors the end
+    fn matches(pattern: &str, path: &str) -> bool {
+        let (pattern, anchored) = match pattern.strip_suffix('$') {
+            Some(pattern) => (pattern, true),
+            None => (pattern, false),
+        };
+        let parts = pattern.split('*').collect::<Vec<_>>();
+        let mut rest = match path.strip_prefix(parts[0]) {
+            Some(rest) => rest,
+            None => return false,
+        };
+        if parts.len() == 1 {
+            return !anchored || rest.is_empty();
+        }
+        for (i, part) in parts.iter().enumerate().skip(1) {
+            if anchored && i == parts.len() - 1 {
+                return rest.ends_with(part);
+            }
+            match rest.find(part) {
+                Some(at) => rest = &rest[at + part.len()..],
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+// === impl RobotsCache ===
+
+impl RobotsCache {
+    pub fn new(client: http::Client) -> RobotsCache {
+        RobotsCache {
+            client,
+            fetched: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The `disallow` rule of the url's `robots.txt` keeping `bot` away from it
+    ///
+    /// Only http(s) urls have a `robots.txt`; one that is missing or cannot be
+    /// read disallows nothing.
+    pub async fn disallowed_by(&self, bot: &str, url: &str) -> Option<String> {
+        let url = Url::parse(url).ok().filter(|url| matches!(url.scheme(), "http" | "https"))?;
+        let robots = self.shared(&url).await;
+        robots.disallowed_by(bot, &url).map(str::to_string)
+    }
+
+    /// Pending or completed fetch of the url's `robots.txt`
+    fn shared(&self, url: &Url) -> Fetch {
+        let origin = url.origin().ascii_serialization();
+        let mut fetched = self.fetched.lock().unwrap();
+        fetched
+            .entry(origin.clone())
+            .or_insert_with(|| RobotsCache::fetch(self.client.clone(), origin).boxed().shared())
+            .clone()
+    }
+
+    async fn fetch(client: http::Client, origin: String) -> Arc<RobotsTxt> {
+        let url = format!("{}/robots.txt", origin);
+        event!(target: CLI, Level::DEBUG, "fetching robots.txt; url={}", url);
+        match client.fetch(Method::GET, &url).await {
+            Ok(Fetched { meta, body }) if meta.status_code().is_success() => {
+                Arc::new(RobotsTxt::parse(&body))
+            }
+            Ok(Fetched { meta, .. }) => {
+                let status = meta.status();
+                event!(target: CLI, Level::DEBUG, "no robots.txt; url={} status={}", url, status);
+                Arc::new(RobotsTxt::default())
+            }
+            Err(err) => {
+                event!(target: CLI, Level::WARN, "robots.txt unreadable; url={} {}", url, err);
+                Arc::new(RobotsTxt::default())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::{Html, HtmlLoader};
+    use crate::http::{Client, MemoryTransport, Reply};
+    use crate::parse::{FromUrl, Parse};
+
+    const ROBOTS: &str = "\
+User-agent: *
+Disallow: /private
+Disallow: /*.pdf$
+
+User-agent: googlebot
+User-agent: bingbot
+Disallow: /shop
+Allow: /shop/sale
+Disallow: /*?sort=
+
+User-agent: googlebot-news
+Disallow: /archive
+
+User-agent: duckduckbot
+Disallow:
+";
+
+    fn url(path: &str) -> Url {
+        Url::parse("http://site.test/").unwrap().join(path).unwrap()
+    }
+
+    /// Document fetched from `url`, which answers with `reply`
+    async fn fetched(url: &str, reply: Reply) -> Parse<Html> {
+        let client = Client::new(MemoryTransport::new().reply(url, reply));
+        Parse::new(HtmlLoader::with_client(client)).from(url).await.unwrap()
+    }
+
+    fn document(body: &str) -> Reply {
+        Reply::new(200).header("Content-Type", "text/html").body(body)
+    }
+
+    #[test]
+    fn rule_matches_prefixes_and_wildcards() {
+        assert!(Rule::matches("/shop", "/shop"));
+        assert!(Rule::matches("/shop", "/shopping/cart"));
+        assert!(!Rule::matches("/shop", "/about/shop"));
+
+        assert!(Rule::matches("/*.pdf", "/docs/a.pdf?dl=1"));
+        assert!(Rule::matches("/*/edit", "/posts/1/edit"));
+        assert!(!Rule::matches("/*/edit", "/posts/1"));
+
+        assert!(Rule::matches("/*.pdf$", "/docs/a.pdf"));
+        assert!(!Rule::matches("/*.pdf$", "/docs/a.pdf?dl=1"));
+        assert!(Rule::matches("/about$", "/about"));
+        assert!(!Rule::matches("/about$", "/about/team"));
+    }
+
+    #[test]
+    fn robots_txt_picks_the_group_of_the_most_specific_agent() {
+        let robots = RobotsTxt::parse(ROBOTS);
+
+        // unnamed crawlers fall back to `*`
+        assert_eq!(robots.disallowed_by("slurp", &url("/private/a")), Some("/private"));
+        assert_eq!(robots.disallowed_by("slurp", &url("/shop")), None);
+
+        // consecutive user-agent lines share a group, and `*` no longer applies
+        assert_eq!(robots.disallowed_by("Googlebot", &url("/shop")), Some("/shop"));
+        assert_eq!(robots.disallowed_by("bingbot", &url("/shop")), Some("/shop"));
+        assert_eq!(robots.disallowed_by("googlebot", &url("/private/a")), None);
+
+        // `googlebot-news` has a group of its own, `googlebot-image` falls back to `googlebot`
+        assert_eq!(robots.disallowed_by("googlebot-news", &url("/archive")), Some("/archive"));
+        assert_eq!(robots.disallowed_by("googlebot-news", &url("/shop")), None);
+        assert_eq!(robots.disallowed_by("googlebot-image", &url("/shop")), Some("/shop"));
+    }
+
+    #[test]
+    fn robots_txt_lets_the_longest_rule_win() {
+        let robots = RobotsTxt::parse(ROBOTS);
+
+        assert_eq!(robots.disallowed_by("googlebot", &url("/shop/sale/shoes")), None);
+        assert_eq!(robots.disallowed_by("googlebot", &url("/shop/shoes")), Some("/shop"));
+
+        // allow wins a tie
+        let robots = RobotsTxt::parse("User-agent: *\nDisallow: /a\nAllow: /a\n");
+        assert_eq!(robots.disallowed_by("googlebot", &url("/a")), None);
+    }
+
+    #[test]
+    fn robots_txt_matches_wildcards_against_the_path_and_query() {
+        let robots = RobotsTxt::parse(ROBOTS);
+
+        assert_eq!(robots.disallowed_by("googlebot", &url("/list?sort=asc")), Some("/*?sort="));
+        assert_eq!(robots.disallowed_by("googlebot", &url("/list?page=2")), None);
+        assert_eq!(robots.disallowed_by("slurp", &url("/docs/a.pdf")), Some("/*.pdf$"));
+        assert_eq!(robots.disallowed_by("slurp", &url("/docs/a.pdf?dl=1")), None);
+    }
+
+    #[test]
+    fn robots_txt_ignores_an_empty_disallow() {
+        let robots = RobotsTxt::parse(ROBOTS);
+        assert_eq!(robots.disallowed_by("duckduckbot", &url("/private/a")), None);
+
+        let robots = RobotsTxt::parse("User-agent: *\nDisallow:\n");
+        assert_eq!(robots.disallowed_by("googlebot", &url("/")), None);
+    }
+
+    #[test]
+    fn x_robots_tag_is_given_to_every_crawler_without_a_prefix() {
+        let meta = RobotsMeta::from_header("noindex, NoFollow");
+        assert_eq!(meta.agent(), None);
+        assert_eq!(meta.directives(), ["noindex", "nofollow"]);
+        assert!(meta.applies_to("googlebot") && meta.applies_to("bingbot"));
+        assert!(meta.is_noindex());
+
+        // a directive carrying a value is not mistaken for a crawler
+        let meta = RobotsMeta::from_header("unavailable_after: 2030-01-01");
+        assert_eq!(meta.agent(), None);
+        assert!(!meta.is_noindex());
+    }
+
+    #[test]
+    fn x_robots_tag_with_a_prefix_is_given_to_that_crawler() {
+        let meta = RobotsMeta::from_header("GoogleBot: none");
+        assert_eq!(meta.agent(), Some("googlebot"));
+        assert!(meta.applies_to("googlebot"));
+        assert!(!meta.applies_to("bingbot"));
+        assert!(meta.is_noindex());
+        assert_eq!(meta.to_string(), "googlebot: none");
+    }
+
+    #[tokio::test]
+    async fn assess_finds_a_page_indexable_without_signals_against_it() {
+        let html = "<link rel=canonical href=/a><meta name=bingbot content=noindex>";
+        let page = fetched("http://site.test/a", document(html)).await;
+
+        let indexability =
+            Indexability::assess(&page, page.get_ref().response(), None, DEFAULT_BOT).unwrap();
+        assert_eq!(indexability.verdict(), Verdict::Indexable);
+        assert_eq!(indexability.reason(), None);
+        assert_eq!(indexability.canonicals(), ["http://site.test/a"]);
+        assert_eq!(indexability.robots().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn assess_honours_noindex_in_meta_tags_and_headers() {
+        let page = Html::inline("<meta name=robots content='noindex, follow'>", None);
+        let indexability = Indexability::assess(&page, None, None, DEFAULT_BOT).unwrap();
+        assert_eq!(indexability.verdict(), Verdict::Noindex);
+        assert_eq!(indexability.reason(), Some("<meta name=\"robots\"> noindex, follow"));
+
+        let reply = document("<p>hi</p>").header("X-Robots-Tag", "googlebot: noindex");
+        let page = fetched("http://site.test/", reply).await;
+        let response = page.get_ref().response();
+
+        let indexability = Indexability::assess(&page, response, None, "googlebot").unwrap();
+        assert_eq!(indexability.verdict(), Verdict::Noindex);
+        assert_eq!(indexability.reason(), Some("x-robots-tag googlebot: noindex"));
+
+        let indexability = Indexability::assess(&page, response, None, "bingbot").unwrap();
+        assert_eq!(indexability.verdict(), Verdict::Indexable);
+        assert_eq!(indexability.x_robots_tag().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn assess_finds_a_page_canonicalized_to_another_url() {
+        let html = "<link rel=canonical href='/b#top'>";
+        let page = fetched("http://site.test/a", document(html)).await;
+
+        let indexability =
+            Indexability::assess(&page, page.get_ref().response(), None, DEFAULT_BOT).unwrap();
+        assert_eq!(indexability.verdict(), Verdict::Canonicalized);
+        assert_eq!(indexability.reason(), Some("canonical is http://site.test/b#top"));
+
+        // without a response the page's own url is unknown
+        let page = Html::inline(html, Some("http://site.test/a"));
+        let indexability = Indexability::assess(&page, None, None, DEFAULT_BOT).unwrap();
+        assert_eq!(indexability.verdict(), Verdict::Indexable);
+    }
+
+    #[tokio::test]
+    async fn assess_puts_robots_txt_before_the_status() {
+        let page = fetched("http://site.test/gone", Reply::new(404)).await;
+        let response = page.get_ref().response();
+
+        let indexability =
+            Indexability::assess(&page, response, Some("/gone"), DEFAULT_BOT).unwrap();
+        assert_eq!(indexability.verdict(), Verdict::Blocked);
+        assert_eq!(indexability.reason(), Some("robots.txt disallows /gone"));
+
+        let indexability = Indexability::assess(&page, response, None, DEFAULT_BOT).unwrap();
+        assert_eq!(indexability.verdict(), Verdict::Unavailable);
+        assert_eq!(indexability.reason(), Some("status 404"));
+    }
+}
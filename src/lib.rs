@@ -1,11 +1,25 @@
+pub mod audit;
+pub mod check;
 pub mod cli;
+pub mod config;
+pub mod crawl;
 pub mod data;
 pub mod error;
+pub mod exit;
+pub mod extract;
 pub mod file;
 pub mod html;
+pub mod http;
+pub mod image;
+pub mod index;
 pub mod logging;
+pub mod outline;
+pub mod output;
 pub mod probe;
+pub mod report;
+pub mod schema;
 pub mod service;
+pub mod social;
 pub mod system;
 pub mod utils;
 
@@ -8,6 +8,7 @@ use tokio::time::Instant;
 use tower::{Layer, Service};
 use tracing::{event, Level};
 
+#[allow(dead_code)]
 pub(crate) const APP: &str = "app";
 pub(crate) const CLI: &str = "cli";
 pub(crate) const JSON: &str = "json";
@@ -107,6 +108,12 @@ impl LoggingLayer {
     pub fn new() -> Self { Self }
 }
 
+impl Default for LoggingLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<S> Layer<S> for LoggingLayer {
     type Service = Logging<S>;
 
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{Finding, Severity};
+use crate::html::{Heading, HtmlParser};
+use crate::utils::Result;
+
+/// Headings of a document in order, along with the problems found in their
+/// hierarchy
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Outline {
+    headings: Vec<Heading>,
+    /// Index of each heading's parent; the closest heading before it with a
+    /// lower level
+    parents: Vec<Option<usize>>,
+    findings: Vec<Finding>,
+}
+
+// === impl Outline ===
+
+impl Outline {
+    /// Reads the document's headings and checks for a missing or repeated h1,
+    /// skipped levels, empty headings and headings sharing the same text
+    pub fn collect<P: HtmlParser>(parser: &P) -> Result<Outline> {
+        let headings = parser.headings()?;
+
+        let mut parents = Vec::with_capacity(headings.len());
+        let mut open: Vec<usize> = Vec::new();
+        for (i, heading) in headings.iter().enumerate() {
+            while open.last().is_some_and(|last| headings[*last].level() >= heading.level()) {
+                open.pop();
+            }
+            parents.push(open.last().copied());
+            open.push(i);
+        }
+
+        let findings = Outline::check(&headings);
+        Ok(Outline { headings, parents, findings })
+    }
+
+    pub fn headings(&self) -> &[Heading] {
+        &self.headings
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Index of each heading's parent, in the order of `headings`
+    pub fn parents(&self) -> &[Option<usize>] {
+        &self.parents
+    }
+
+    fn check(headings: &[Heading]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let h1 = headings.iter().filter(|h| h.level() == 1).count();
+        if h1 == 0 {
+            findings.push(Finding::new(
+                "missing-h1",
+                Severity::Warning,
+                "page has no h1".to_string(),
+            ));
+        } else if h1 > 1 {
+            findings.push(Finding::new(
+                "multiple-h1",
+                Severity::Warning,
+                format!("page has {} h1 headings", h1),
+            ));
+        }
+
+        for pair in headings.windows(2) {
+            let (previous, heading) = (&pair[0], &pair[1]);
+            if heading.level() > previous.level() + 1 {
+                findings.push(Finding::new(
+                    "skipped-heading-level",
+                    Severity::Warning,
+                    format!(
+                        "{} {:?} follows {} {:?}",
+                        heading.tag(),
+                        heading.text(),
+                        previous.tag(),
+                        previous.text()
+                    ),
+                ));
+            }
+        }
+
+        for heading in headings.iter().filter(|h| h.text().is_empty()) {
+            findings.push(Finding::new(
+                "empty-heading",
+                Severity::Warning,
+                format!("{} at position {} has no text", heading.tag(), heading.position()),
+            ));
+        }
+
+        // reported once per text, at the first heading using it
+        let key = |heading: &Heading| heading.text().to_lowercase();
+        for (i, heading) in headings.iter().enumerate().filter(|(_, h)| !h.text().is_empty()) {
+            if headings[..i].iter().any(|h| key(h) == key(heading)) {
+                continue;
+            }
+            let positions = headings[i..]
+                .iter()
+                .filter(|h| key(h) == key(heading))
+                .map(|h| h.position().to_string())
+                .collect::<Vec<_>>();
+            if positions.len() > 1 {
+                findings.push(Finding::new(
+                    "duplicate-heading",
+                    Severity::Info,
+                    format!(
+                        "{:?} is used by {} headings, at positions {}",
+                        heading.text(),
+                        positions.len(),
+                        positions.join(", ")
+                    ),
+                ));
+            }
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::Html;
+
+    const SKIPPING: &str = "<h1>Guide</h1><h3>Install</h3><h4>Deps</h4><h2>Usage</h2>
+        <h3>Flags</h3><h1>Reference</h1><h4>Options</h4>";
+
+    #[test]
+    fn headings_hang_from_the_closest_lower_level_before_them() {
+        let outline = Outline::collect(&Html::inline(SKIPPING, None)).unwrap();
+
+        let headings = outline
+            .headings()
+            .iter()
+            .map(|h| (h.level(), h.text(), h.position()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headings,
+            [
+                (1, "Guide", 1),
+                (3, "Install", 2),
+                (4, "Deps", 3),
+                (2, "Usage", 4),
+                (3, "Flags", 5),
+                (1, "Reference", 6),
+                (4, "Options", 7),
+            ]
+        );
+        assert_eq!(outline.parents(), [None, Some(0), Some(1), Some(0), Some(3), None, Some(5)]);
+    }
+
+    #[test]
+    fn skipped_levels_and_several_h1_are_findings() {
+        let outline = Outline::collect(&Html::inline(SKIPPING, None)).unwrap();
+
+        let findings = outline
+            .findings()
+            .iter()
+            .map(|f| (f.rule(), f.message()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                ("multiple-h1", "page has 2 h1 headings"),
+                ("skipped-heading-level", "h3 \"Install\" follows h1 \"Guide\""),
+                ("skipped-heading-level", "h4 \"Options\" follows h1 \"Reference\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_and_repeated_headings_are_findings() {
+        let html = "<h2>FAQ</h2><h1> </h1><h2>faq</h2>";
+        let outline = Outline::collect(&Html::inline(html, None)).unwrap();
+
+        let findings = outline.findings().iter().map(|f| f.message()).collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                "h1 at position 2 has no text",
+                "\"FAQ\" is used by 2 headings, at positions 1, 3",
+            ]
+        );
+    }
+}
@@ -0,0 +1,674 @@
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::audit::Finding;
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::html::RobotsMeta;
+use crate::image::ImageFile;
+use crate::report::{PageReport, ProbeData, ProbeReport, Report};
+use crate::utils::Result;
+
+/// Single flattened row of output, keyed by column name
+pub type Record = LinkedHashMap<String, Value>;
+
+/// Columns added to a record beyond the ones every row of a report shares
+type Columns<'a> = Vec<(&'a str, Value)>;
+
+/// Converts a report into flat output records
+pub trait IntoRecords {
+    fn records(&self) -> Vec<Record>;
+}
+
+/// Supported output formats
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Json,
+    Ndjson,
+    Table,
+}
+
+/// Writes records to the underlying sink in the selected format
+///
+/// `json` and `ndjson` are written as each record arrives; `csv` and `table`
+/// are buffered until `finish` so every column any record has makes it into
+/// the header.
+pub struct Output {
+    format: Format,
+    sink: Box<dyn Write + Send>,
+    columns: Vec<String>,
+    rows: Vec<Record>,
+    written: usize,
+}
+
+// === impl Format ===
+
+impl Format {
+    pub const VARIANTS: [&'static str; 4] = ["csv", "json", "ndjson", "table"];
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Csv => write!(f, "csv"),
+            Format::Json => write!(f, "json"),
+            Format::Ndjson => write!(f, "ndjson"),
+            Format::Table => write!(f, "table"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "table" => Ok(Format::Table),
+            _ => Err(Error::new(ErrorType::Message(
+                ErrorKind::InvalidParameters,
+                format!("unknown output format {}", s),
+            ))),
+        }
+    }
+}
+
+// === impl Output ===
+
+impl Output {
+    pub fn new(format: Format, sink: Box<dyn Write + Send>) -> Output {
+        Output {
+            format,
+            sink,
+            columns: Vec::new(),
+            rows: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Writes to stdout, or to the file at `path` when specified
+    pub fn create(format: Format, path: Option<&str>) -> Result<Output> {
+        let sink: Box<dyn Write + Send> = match path {
+            Some(path) => match std::fs::File::create(path) {
+                Ok(file) => Box::new(std::io::BufWriter::new(file)),
+                Err(err) => {
+                    return Err(Error::new(ErrorType::Message(
+                        ErrorKind::Create,
+                        format!("{}; {}", path, err),
+                    )))
+                }
+            },
+            None => Box::new(std::io::stdout()),
+        };
+        Ok(Output::new(format, sink))
+    }
+
+    pub fn write<R: IntoRecords>(&mut self, report: &R) -> Result<()> {
+        for record in report.records() {
+            self.write_record(record)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_record(&mut self, record: Record) -> Result<()> {
+        for key in record.keys() {
+            if !self.columns.contains(key) {
+                // `error` stays the last column whatever is added after it
+                let at = self.columns.iter().position(|c| c == "error").unwrap_or(self.columns.len());
+                self.columns.insert(at, key.clone());
+            }
+        }
+
+        match self.format {
+            Format::Json => {
+                let prefix = if self.written == 0 { "[\n  " } else { ",\n  " };
+                let json = Output::to_json(&record)?;
+                self.raw(&format!("{}{}", prefix, json))?;
+            }
+            Format::Ndjson => {
+                let json = Output::to_json(&record)?;
+                self.line(&json)?;
+            }
+            Format::Csv | Format::Table => self.rows.push(record),
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Flushes any buffered records and closes the output
+    pub fn finish(mut self) -> Result<()> {
+        match self.format {
+            Format::Json if self.written == 0 => self.line("[]")?,
+            Format::Json => self.raw("\n]\n")?,
+            Format::Csv => self.csv()?,
+            Format::Table => self.table()?,
+            Format::Ndjson => {}
+        }
+        self.sink.flush().map_err(Output::io_error)
+    }
+
+    fn csv(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.rows);
+        let header = self.columns.iter().map(|c| Output::escape_csv(c)).collect::<Vec<_>>();
+        self.line(&header.join(","))?;
+        for record in rows {
+            let row = self
+                .columns
+                .iter()
+                .map(|c| Output::escape_csv(&Output::cell(record.get(c))))
+                .collect::<Vec<_>>();
+            self.line(&row.join(","))?;
+        }
+        Ok(())
+    }
+
+    fn table(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.rows);
+        let mut cells = rows
+            .iter()
+            .map(|r| self.columns.iter().map(|c| Output::cell(r.get(c))).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // headings read as a tree, drawn ahead of their tag
+        if let Some(item) = self.columns.iter().position(|c| c == "item") {
+            for (row, branch) in cells.iter_mut().zip(Output::branches(&rows)) {
+                row[item].insert_str(0, &branch);
+            }
+        }
+        let widths = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| cells.iter().map(|r| r[i].chars().count()).fold(c.len(), usize::max))
+            .collect::<Vec<_>>();
+
+        let pad = |values: &[String]| {
+            values
+                .iter()
+                .zip(&widths)
+                .map(|(v, w)| format!("{:<width$}", v, width = w))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        let header = self.columns.iter().map(|c| c.to_uppercase()).collect::<Vec<_>>();
+        let rule = widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>();
+        self.line(&pad(&header))?;
+        self.line(&pad(&rule))?;
+        for row in cells {
+            self.line(&pad(&row))?;
+        }
+        Ok(())
+    }
+
+    /// Branch each heading hangs from, such as `│  └─ `, drawn from the
+    /// `parent` and `position` columns of consecutive headings of a source;
+    /// other rows and top level headings have none
+    fn branches(rows: &[Record]) -> Vec<String> {
+        let number = |record: &Record, column: &str| record.get(column).and_then(Value::as_u64);
+        let mut branches = vec![String::new(); rows.len()];
+        let mut start = 0;
+        while start < rows.len() {
+            let source = rows[start].get("source");
+            let len = rows[start..]
+                .iter()
+                .take_while(|r| r.contains_key("parent") && r.get("source") == source)
+                .count();
+            let run = &rows[start..start + len];
+            let parents = run
+                .iter()
+                .map(|r| {
+                    let parent = number(r, "parent")?;
+                    run.iter().position(|p| number(p, "position") == Some(parent))
+                })
+                .collect::<Vec<_>>();
+            // whether a later heading shares the parent, for each heading
+            let followed =
+                (0..len).map(|i| parents[i + 1..].contains(&parents[i])).collect::<Vec<_>>();
+            for i in 0..len {
+                let mut ancestors = Vec::new();
+                let mut parent = parents[i];
+                while let Some(p) = parent {
+                    ancestors.push(p);
+                    parent = parents[p];
+                }
+                let branch = &mut branches[start + i];
+                // top level headings are drawn without a branch of their own
+                for ancestor in ancestors.iter().rev().skip(1) {
+                    branch.push_str(if followed[*ancestor] { "│  " } else { "   " });
+                }
+                if !ancestors.is_empty() {
+                    branch.push_str(if followed[i] { "├─ " } else { "└─ " });
+                }
+            }
+            start += len.max(1);
+        }
+        branches
+    }
+
+    /// Row of a finding; the rule as item and the message as value
+    fn finding(finding: &Finding) -> (String, Value, Columns<'static>) {
+        let severity = vec![("severity", Value::from(finding.severity().to_string()))];
+        (finding.rule().to_string(), Value::from(finding.message()), severity)
+    }
+
+    fn cell(value: Option<&Value>) -> String {
+        match value {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+        }
+    }
+
+    fn escape_csv(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Several values of a single column, as one cell
+    fn joined(values: &[String]) -> Value {
+        Value::from(values.join(" | "))
+    }
+
+    fn to_json(record: &Record) -> Result<String> {
+        serde_json::to_string(record).map_err(|err| {
+            Error::new(ErrorType::Message(ErrorKind::Parse, err.to_string()))
+        })
+    }
+
+    fn line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.sink, "{}", line).map_err(Output::io_error)
+    }
+
+    fn raw(&mut self, text: &str) -> Result<()> {
+        write!(self.sink, "{}", text).map_err(Output::io_error)
+    }
+
+    fn io_error(err: std::io::Error) -> Error {
+        Error::new(ErrorType::Message(
+            ErrorKind::Io,
+            format!("unable to write output; {}", err),
+        ))
+    }
+}
+
+// === impl IntoRecords ===
+
+impl IntoRecords for ProbeReport {
+    fn records(&self) -> Vec<Record> {
+        // columns specific to the kind of item are placed before `error`
+        let record = |item: Value, value: Value, extra: Columns| {
+            let mut record = Record::new();
+            record.insert("source".to_string(), Value::from(self.source()));
+            record.insert("origin".to_string(), Value::from(self.origin().to_string()));
+            record.insert("item".to_string(), item);
+            record.insert("value".to_string(), value);
+            for (column, value) in extra {
+                record.insert(column.to_string(), value);
+            }
+            for extracted in self.extracted() {
+                record.insert(extracted.column(), Output::joined(extracted.values()));
+            }
+            record.insert("error".to_string(), Value::from(self.error()));
+            record
+        };
+
+        let data = match self.data() {
+            Some(data) => data,
+            None => return vec![record(Value::Null, Value::Null, Vec::new())],
+        };
+        let kind = match data {
+            ProbeData::Findings(_) => "finding",
+            ProbeData::LinkChecks(_) => "link",
+            ProbeData::Descriptions(_) => "description",
+            ProbeData::Headers(_) => "header",
+            ProbeData::Images(_) => "image",
+            ProbeData::Indexability(_) => "indexability",
+            ProbeData::Links(_) => "link",
+            ProbeData::Outline(_) => "heading",
+            ProbeData::PageTitle(_) => "title",
+            ProbeData::Response(_) => "response",
+            ProbeData::Matches(_) => "match",
+            ProbeData::Social(_) => "social",
+            ProbeData::StructuredData(_) => "structured-data",
+        };
+        let mut items: Vec<(String, Value, Columns)> = match data {
+            // added below, along with the findings of every other query
+            ProbeData::Findings(_) => Vec::new(),
+            ProbeData::LinkChecks(checks) => checks
+                .iter()
+                .map(|check| {
+                    let extra = vec![
+                        ("kind", Value::from(check.kind().to_string())),
+                        ("outcome", Value::from(check.outcome().to_string())),
+                        ("status", Value::from(check.status())),
+                        ("redirects", Value::from(check.redirects().join(" -> "))),
+                        ("reason", Value::from(check.reason())),
+                    ];
+                    ("link".to_string(), Value::from(check.url()), extra)
+                })
+                .collect(),
+            ProbeData::Descriptions(d) => d
+                .iter()
+                .map(|v| ("description".to_string(), Value::from(v.as_str()), Vec::new()))
+                .collect(),
+            ProbeData::Headers(headers) => headers
+                .iter()
+                .flat_map(|h| {
+                    let tag = h.tag().to_string();
+                    h.iter().map(move |v| (tag.clone(), Value::from(v.as_str()), Vec::new()))
+                })
+                .collect(),
+            ProbeData::Images(report) => report
+                .images()
+                .iter()
+                .map(|image| {
+                    let file = report.file(image);
+                    let content_type = file.and_then(ImageFile::content_type);
+                    let extra = vec![
+                        ("src", Value::from(image.src())),
+                        ("srcset", Value::from(image.srcset())),
+                        ("alt", Value::from(image.alt())),
+                        ("width", Value::from(image.width())),
+                        ("height", Value::from(image.height())),
+                        ("loading", Value::from(image.loading())),
+                        ("bytes", Value::from(file.and_then(ImageFile::bytes))),
+                        ("content-type", Value::from(content_type.or(image.media_type()))),
+                    ];
+                    (image.tag().to_string(), Value::from(image.url()), extra)
+                })
+                .collect(),
+            ProbeData::Indexability(index) => {
+                let joined = |metas: &[RobotsMeta]| {
+                    Output::joined(&metas.iter().map(|m| m.to_string()).collect::<Vec<_>>())
+                };
+                let extra = vec![
+                    ("reason", Value::from(index.reason())),
+                    ("canonical", Output::joined(index.canonicals())),
+                    ("robots", joined(index.robots())),
+                    ("x-robots-tag", joined(index.x_robots_tag())),
+                ];
+                vec![("indexability".to_string(), Value::from(index.verdict().to_string()), extra)]
+            }
+            ProbeData::Links(links) => links
+                .iter()
+                .map(|link| {
+                    let extra = vec![
+                        ("kind", Value::from(link.kind().to_string())),
+                        ("text", Value::from(link.text())),
+                        ("rel", Value::from(link.rel().join(" "))),
+                        ("target", Value::from(link.target())),
+                        ("href", Value::from(link.href())),
+                    ];
+                    ("link".to_string(), Value::from(link.url()), extra)
+                })
+                .collect(),
+            ProbeData::Outline(outline) => {
+                let headings = outline.headings();
+                headings
+                    .iter()
+                    .zip(outline.parents())
+                    .map(|(h, parent)| {
+                        let parent = parent.map(|p| headings[p].position());
+                        let extra = vec![
+                            ("level", Value::from(h.level())),
+                            ("position", Value::from(h.position())),
+                            ("parent", Value::from(parent)),
+                        ];
+                        (h.tag().to_string(), Value::from(h.text()), extra)
+                    })
+                    .collect()
+            }
+            ProbeData::PageTitle(t) => t
+                .iter()
+                .map(|v| ("title".to_string(), Value::from(v.as_str()), Vec::new()))
+                .collect(),
+            ProbeData::Matches(matches) => matches
+                .iter()
+                .map(|v| ("match".to_string(), Value::from(v.as_str()), Vec::new()))
+                .collect(),
+            ProbeData::Social(preview) => preview
+                .social()
+                .properties()
+                .into_iter()
+                .map(|(property, value)| (property.to_string(), Value::from(value), Vec::new()))
+                .collect(),
+            ProbeData::StructuredData(data) => data
+                .items()
+                .iter()
+                .map(|item| {
+                    let mut properties: Vec<&str> = Vec::new();
+                    for (name, _) in item.properties() {
+                        if !properties.contains(&name.as_str()) {
+                            properties.push(name);
+                        }
+                    }
+                    let extra = vec![("properties", Value::from(properties.join(", ")))];
+                    (item.syntax().to_string(), Value::from(item.types().join(", ")), extra)
+                })
+                .collect(),
+            ProbeData::Response(response) => {
+                let timing = |d: std::time::Duration| Value::from(d.as_millis() as u64);
+                let mut items = vec![
+                    ("status".to_string(), Value::from(response.status()), Vec::new()),
+                    ("url".to_string(), Value::from(response.url()), Vec::new()),
+                ];
+                for url in response.redirects() {
+                    items.push(("redirect".to_string(), Value::from(url.as_str()), Vec::new()));
+                }
+                items.push(("content-type".to_string(), Value::from(response.content_type()), Vec::new()));
+                items.push(("content-length".to_string(), Value::from(response.content_length()), Vec::new()));
+                items.push(("ttfb_ms".to_string(), timing(response.ttfb()), Vec::new()));
+                items.push(("total_ms".to_string(), timing(response.total()), Vec::new()));
+                for (name, value) in response.headers() {
+                    items.push((format!("header:{}", name), Value::from(value.as_str()), Vec::new()));
+                }
+                items
+            }
+        };
+
+        items.extend(data.findings().iter().map(Output::finding));
+
+        if items.is_empty() {
+            return vec![record(Value::from(kind), Value::Null, Vec::new())];
+        }
+        items
+            .into_iter()
+            .map(|(item, value, extra)| record(Value::from(item), value, extra))
+            .collect()
+    }
+}
+
+impl IntoRecords for PageReport {
+    fn records(&self) -> Vec<Record> {
+        let headers = self
+            .headers()
+            .iter()
+            .filter(|h| !h.is_empty())
+            .map(|h| (h.tag().to_string(), Value::from(h.to_vec())))
+            .collect::<serde_json::Map<_, _>>();
+
+        let mut record = Record::new();
+        record.insert("url".to_string(), Value::from(self.url()));
+        record.insert("depth".to_string(), Value::from(self.depth()));
+        record.insert("status".to_string(), Value::from(self.status()));
+        record.insert("title".to_string(), Value::from(self.title().join(" | ")));
+        record.insert("description".to_string(), Value::from(self.descriptions().join(" | ")));
+        let index = self.indexability();
+        let canonicals = index.map(|i| i.canonicals().to_vec()).unwrap_or_default();
+        record.insert("canonical".to_string(), Output::joined(&canonicals));
+        let verdict = index.map(|i| i.verdict().to_string());
+        record.insert("indexability".to_string(), Value::from(verdict));
+        let reason = index.and_then(|i| i.reason());
+        record.insert("indexability_reason".to_string(), Value::from(reason));
+        for extracted in self.extracted() {
+            record.insert(extracted.column(), Output::joined(extracted.values()));
+        }
+        record.insert("headers".to_string(), Value::Object(headers));
+        record.insert("links".to_string(), Value::from(self.links().to_vec()));
+        record.insert("error".to_string(), Value::from(self.error()));
+        vec![record]
+    }
+}
+
+impl IntoRecords for Report {
+    fn records(&self) -> Vec<Record> {
+        match self {
+            Report::Page(report) => report.records(),
+            Report::Probe(report) => report.records(),
+        }
+    }
+}
+
+impl<R: IntoRecords> IntoRecords for Vec<R> {
+    fn records(&self) -> Vec<Record> {
+        self.iter().flat_map(|r| r.records()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::data::Origin;
+    use crate::html::Html;
+    use crate::outline::Outline;
+
+    /// Sink that can still be read once the output owning it is finished
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(columns: &[(&str, Value)]) -> Record {
+        columns.iter().map(|(c, v)| (c.to_string(), v.clone())).collect()
+    }
+
+    /// Everything written for `records` in `format`
+    fn written(format: Format, records: Vec<Record>) -> String {
+        let buffer = Buffer::default();
+        let mut output = Output::new(format, Box::new(buffer.clone()));
+        for record in records {
+            output.write_record(record).unwrap();
+        }
+        output.finish().unwrap();
+        let written = buffer.0.lock().unwrap().clone();
+        String::from_utf8(written).unwrap()
+    }
+
+    fn outline(source: &str, html: &str) -> Vec<Record> {
+        let outline = Outline::collect(&Html::inline(html, None)).unwrap();
+        ProbeReport::new(Origin::FileSystem, source, ProbeData::Outline(outline)).records()
+    }
+
+    #[test]
+    fn branches_draw_the_outline_of_each_source() {
+        let mut rows = outline(
+            "a.html",
+            "<h1>Guide</h1><h3>Install</h3><h4>Deps</h4><h2>Usage</h2>
+             <h3>Flags</h3><h1>Reference</h1><h4>Options</h4>",
+        );
+        rows.extend(outline("b.html", "<h1>Home</h1><h2>News</h2>"));
+
+        let items = rows.iter().map(|r| Output::cell(r.get("item"))).collect::<Vec<_>>();
+        let tree = Output::branches(&rows)
+            .into_iter()
+            .zip(items)
+            .map(|(branch, item)| format!("{}{}", branch, item))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tree,
+            [
+                "h1",
+                "├─ h3",
+                "│  └─ h4",
+                "└─ h2",
+                "   └─ h3",
+                "h1",
+                "└─ h4",
+                // findings are rows of their own, outside the tree
+                "multiple-h1",
+                "skipped-heading-level",
+                "skipped-heading-level",
+                "h1",
+                "└─ h2",
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_quotes_cells_with_separators_quotes_or_newlines() {
+        let rows = vec![record(&[
+            ("plain", Value::from("a b")),
+            ("comma", Value::from("a,b")),
+            ("quote", Value::from("say \"hi\"")),
+            ("newline", Value::from("a\nb")),
+            ("number", Value::from(3)),
+            ("empty", Value::Null),
+        ])];
+
+        assert_eq!(
+            written(Format::Csv, rows),
+            "plain,comma,quote,newline,number,empty\na b,\"a,b\",\"say \"\"hi\"\"\",\"a\nb\",3,\n"
+        );
+    }
+
+    #[test]
+    fn csv_header_carries_every_column_of_every_row() {
+        let rows = vec![
+            record(&[("source", Value::from("a")), ("error", Value::Null)]),
+            record(&[("source", Value::from("b")), ("status", Value::from(200))]),
+            record(&[("extract.price", Value::from("9")), ("source", Value::from("c"))]),
+        ];
+
+        // `error` stays last whatever is added after it
+        assert_eq!(
+            written(Format::Csv, rows),
+            "source,status,extract.price,error\na,,,\nb,200,,\nc,,9,\n"
+        );
+        assert_eq!(written(Format::Csv, Vec::new()), "");
+    }
+
+    #[test]
+    fn json_is_a_single_array_whatever_the_number_of_records() {
+        assert_eq!(written(Format::Json, Vec::new()), "[]\n");
+
+        let one = written(Format::Json, vec![record(&[("a", Value::from(1))])]);
+        assert_eq!(one, "[\n  {\"a\":1}\n]\n");
+
+        let rows = (0..3).map(|i| record(&[("a", Value::from(i))])).collect();
+        let several = written(Format::Json, rows);
+        let parsed: Vec<Record> = serde_json::from_str(&several).unwrap();
+        let values = parsed.iter().map(|r| Output::cell(r.get("a"))).collect::<Vec<_>>();
+        assert_eq!(values, ["0", "1", "2"]);
+    }
+}
+
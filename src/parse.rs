@@ -1,16 +1,43 @@
 use crate::error::{Error, ErrorKind};
-use crate::html::{Headers, HtmlAttribute, HtmlDocument, HtmlParser, HtmlTag};
+use crate::html::{
+    AnchorWithin, Headers, Heading, HtmlAttribute, HtmlDocument, HtmlParser, HtmlTag, Image, Link,
+    RobotsMeta, Selection,
+};
+use crate::schema::Item;
+use crate::social::Social;
 use crate::utils::Result;
 use async_trait::async_trait;
-use select::predicate::{Name, Predicate};
+use reqwest::Url;
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Attr, Name, Predicate};
 use std::path::PathBuf;
+use tokio::io::AsyncRead;
 
 #[async_trait]
 pub trait FromPath
 where
     Self: Sized,
 {
-    async fn from(&mut self, path: &str, capacity: usize) -> Result<Self>;
+    /// What reading a document produces
+    type Output;
+
+    async fn from(&mut self, path: &str, capacity: usize) -> Result<Self::Output>;
+}
+
+#[async_trait]
+pub trait FromReader
+where
+    Self: Sized,
+{
+    /// What reading a document produces
+    type Output;
+
+    async fn read<R: AsyncRead + Unpin + Send>(
+        &mut self,
+        reader: R,
+        capacity: usize,
+    ) -> Result<Self::Output>;
 }
 
 #[async_trait]
@@ -18,7 +45,10 @@ pub trait FromUrl
 where
     Self: Sized,
 {
-    async fn from(&mut self, url: &str) -> Result<Self>;
+    /// What reading a document produces
+    type Output;
+
+    async fn from(&mut self, url: &str) -> Result<Self::Output>;
 }
 
 pub trait Parser {}
@@ -27,12 +57,44 @@ pub struct Default;
 
 pub struct Utils;
 impl Utils {
-    fn fix_link(link: &str) -> &str {
-        link.trim_start_matches("https")
-            .trim_start_matches("http")
-            .trim_start_matches(":")
-            .trim_start_matches("//")
-            .trim_end_matches('/')
+    /// Base url links are resolved against; `<base href>` when present,
+    /// otherwise the document's own location
+    fn base_url(doc: &Document, url: Option<&Url>) -> Option<Url> {
+        let href = doc
+            .find(Name(HtmlTag::Base.into()))
+            .find_map(|n| n.attr(HtmlAttribute::Href.into()));
+        match (href, url) {
+            (Some(href), Some(url)) => url.join(href.trim()).ok().or_else(|| Some(url.clone())),
+            (Some(href), None) => Url::parse(href.trim()).ok(),
+            (None, url) => url.cloned(),
+        }
+    }
+
+    /// Absolute, normalized form of `href`; left as written when it is
+    /// relative and there is nothing to resolve it against
+    fn resolve(base: Option<&Url>, href: &str) -> String {
+        let href = href.trim();
+        let url = match base {
+            Some(base) => base.join(href),
+            None => Url::parse(href),
+        };
+        match url {
+            Ok(url) => url.into(),
+            Err(_) => href.to_string(),
+        }
+    }
+
+    /// True when an ancestor of `node` has the attribute
+    fn within(node: &Node, attr: HtmlAttribute) -> bool {
+        let attr: &str = attr.into();
+        let mut parent = node.parent();
+        while let Some(n) = parent {
+            if n.attr(attr).is_some() {
+                return true;
+            }
+            parent = n.parent();
+        }
+        false
     }
 
     fn path_to_string(path: PathBuf) -> String {
@@ -52,6 +114,10 @@ impl<T> Parse<T> {
     pub fn new(kind: T) -> Parse<T> {
         Parse { parse: kind }
     }
+
+    pub fn get_ref(&self) -> &T {
+        &self.parse
+    }
 }
 
 impl<T> Parser for Parse<T> {}
@@ -61,10 +127,26 @@ impl<T> FromPath for Parse<T>
 where
     T: FromPath + Send,
 {
-    async fn from(&mut self, path: &str, capacity: usize) -> Result<Self> {
-        Ok(Self {
-            parse: self.parse.from(path, capacity).await?,
-        })
+    type Output = Parse<T::Output>;
+
+    async fn from(&mut self, path: &str, capacity: usize) -> Result<Self::Output> {
+        Ok(Parse::new(self.parse.from(path, capacity).await?))
+    }
+}
+
+#[async_trait]
+impl<T> FromReader for Parse<T>
+where
+    T: FromReader + Send,
+{
+    type Output = Parse<T::Output>;
+
+    async fn read<R: AsyncRead + Unpin + Send>(
+        &mut self,
+        reader: R,
+        capacity: usize,
+    ) -> Result<Self::Output> {
+        Ok(Parse::new(self.parse.read(reader, capacity).await?))
     }
 }
 
@@ -73,10 +155,10 @@ impl<T> FromUrl for Parse<T>
 where
     T: FromUrl + Send,
 {
-    async fn from(&mut self, url: &str) -> Result<Self> {
-        Ok(Self {
-            parse: self.parse.from(url).await?,
-        })
+    type Output = Parse<T::Output>;
+
+    async fn from(&mut self, url: &str) -> Result<Self::Output> {
+        Ok(Parse::new(self.parse.from(url).await?))
     }
 }
 
@@ -85,31 +167,50 @@ where
     T: HtmlDocument,
 {
     pub fn all_headers(&self, mut buff: Vec<Headers>) -> Result<Vec<Headers>> {
-        let mut count = 1;
-        loop {
-            match count {
-                1 => buff.push(self.header(HtmlTag::H1)?),
-                2 => buff.push(self.header(HtmlTag::H2)?),
-                3 => buff.push(self.header(HtmlTag::H3)?),
-                4 => buff.push(self.header(HtmlTag::H4)?),
-                5 => buff.push(self.header(HtmlTag::H5)?),
-                6 => buff.push(self.header(HtmlTag::H6)?),
-                _ => break,
-            };
-            count = count + 1;
-        }
+        buff.extend(self.headers(&HtmlTag::HEADERS)?);
         Ok(buff)
     }
 
-    pub fn all_links(&self) -> Result<Vec<String>> {
+    pub fn all_links(&self) -> Result<Vec<Link>> {
         self.links(Name(HtmlAttribute::A.into()))
     }
+
+    pub fn all_hrefs(&self) -> Result<Vec<String>> {
+        self.hrefs(Name(HtmlAttribute::A.into()))
+    }
+
+    /// Returns the headers for each of the specified levels
+    pub fn headers(&self, tags: &[HtmlTag]) -> Result<Vec<Headers>> {
+        tags.iter().map(|tag| self.header(*tag)).collect()
+    }
+
+    /// Returns the links nested within any of the specified tags
+    pub fn links_within(&self, tags: &[HtmlTag]) -> Result<Vec<Link>> {
+        self.links(AnchorWithin::new(tags))
+    }
 }
 
 impl<T> HtmlParser for Parse<T>
 where
     T: HtmlDocument,
 {
+    fn canonicals(&self) -> Result<Vec<String>> {
+        if let Ok(doc) = self.parse.document() {
+            let base = Utils::base_url(doc, self.parse.url());
+            Ok(doc
+                .find(Name(HtmlTag::Link.into()))
+                .filter(|n| {
+                    let rel = n.attr(HtmlAttribute::Rel.into()).unwrap_or_default();
+                    rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("canonical"))
+                })
+                .filter_map(|n| n.attr(HtmlAttribute::Href.into()))
+                .map(|href| Utils::resolve(base.as_ref(), href))
+                .collect())
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
+    }
+
     fn descriptions(&self) -> Result<Vec<String>> {
         if let Ok(doc) = self.parse.document() {
             Ok(doc
@@ -117,7 +218,7 @@ where
                 .filter_map(|n| match n.attr(HtmlAttribute::Name.into()) {
                     Some(name) => {
                         if name.contains("description") {
-                            Some(n.attr(HtmlAttribute::Content.into()).unwrap())
+                            n.attr(HtmlAttribute::Content.into())
                         } else {
                             None
                         }
@@ -135,7 +236,7 @@ where
         if let Ok(doc) = self.parse.document() {
             Ok(Headers::new(
                 doc.find(Name(&header.to_string()[..]))
-                    .filter_map(|n| Some(n.text()))
+                    .map(|n| n.text())
                     .collect(),
                 header.into(),
             ))
@@ -144,17 +245,96 @@ where
         }
     }
 
-    fn links<P: Predicate>(&self, predicate: P) -> Result<Vec<String>> {
+    fn headings(&self) -> Result<Vec<Heading>> {
+        if let Ok(doc) = self.parse.document() {
+            let level = |n: &Node| match n.name()?.strip_prefix('h')?.parse::<u8>() {
+                Ok(level @ 1..=6) => Some(level),
+                _ => None,
+            };
+            Ok(doc
+                .find(|n: &Node| level(n).is_some())
+                .enumerate()
+                .map(|(i, n)| Heading::new(level(&n).unwrap_or_default(), &n.text(), i + 1))
+                .collect())
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
+    }
+
+    fn images(&self) -> Result<Vec<Image>> {
+        if let Ok(doc) = self.parse.document() {
+            let base = Utils::base_url(doc, self.parse.url());
+            let img: &str = HtmlTag::Img.into();
+            let source: &str = HtmlTag::Source.into();
+            let picture: &str = HtmlTag::Picture.into();
+            Ok(doc
+                .find(|n: &Node| match n.name() {
+                    Some(name) if name == img => true,
+                    // sources of audio and video are not images
+                    Some(name) if name == source => {
+                        n.parent().and_then(|p| p.name()) == Some(picture)
+                    }
+                    _ => false,
+                })
+                .map(|n| {
+                    let src = n.attr(HtmlAttribute::Src.into()).filter(|s| !s.trim().is_empty());
+                    let candidate = n
+                        .attr(HtmlAttribute::Srcset.into())
+                        .and_then(|srcset| srcset.split(',').next())
+                        .and_then(|candidate| candidate.split_whitespace().next());
+                    let url = src.or(candidate).map(|href| Utils::resolve(base.as_ref(), href));
+                    Image::new(&n, url)
+                })
+                .collect())
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
+    }
+
+    fn json_ld(&self) -> Result<Vec<String>> {
         if let Ok(doc) = self.parse.document() {
+            Ok(doc
+                .find(Name(HtmlTag::Script.into()))
+                .filter(|n| match n.attr(HtmlAttribute::Type.into()) {
+                    Some(kind) => kind.trim().eq_ignore_ascii_case("application/ld+json"),
+                    None => false,
+                })
+                .map(|n| n.text())
+                .collect())
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
+    }
+
+    fn links<P: Predicate>(&self, predicate: P) -> Result<Vec<Link>> {
+        if let Ok(doc) = self.parse.document() {
+            // links are classified against the base url, which is the
+            // page's own url unless a `<base href>` says otherwise
+            let base = Utils::base_url(doc, self.parse.url());
             Ok(doc
                 .find(predicate)
                 .filter_map(|n| {
-                    if let Some(link) = n.attr(HtmlAttribute::Href.into()) {
-                        Some(Parse::<Utils>::fix_link(link))
-                    } else {
-                        None
-                    }
+                    let href = n.attr(HtmlAttribute::Href.into())?;
+                    Some(Link::new(
+                        href,
+                        Utils::resolve(base.as_ref(), href),
+                        &n.text(),
+                        n.attr(HtmlAttribute::Rel.into()),
+                        n.attr(HtmlAttribute::Target.into()),
+                        base.as_ref(),
+                    ))
                 })
+                .collect())
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
+    }
+
+    fn hrefs<P: Predicate>(&self, predicate: P) -> Result<Vec<String>> {
+        if let Ok(doc) = self.parse.document() {
+            Ok(doc
+                .find(predicate)
+                .filter_map(|n| n.attr(HtmlAttribute::Href.into()))
                 .map(|x| x.to_string())
                 .collect())
         } else {
@@ -162,6 +342,22 @@ where
         }
     }
 
+    fn microdata(&self) -> Result<Vec<Item>> {
+        if let Ok(doc) = self.parse.document() {
+            // items that are the value of another item's property belong to it
+            Ok(doc
+                .find(Attr(HtmlAttribute::ItemScope.into(), ()))
+                .filter(|n| {
+                    n.attr(HtmlAttribute::ItemProp.into()).is_none()
+                        || !Utils::within(n, HtmlAttribute::ItemScope)
+                })
+                .map(|n| Item::from_microdata(&n))
+                .collect())
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
+    }
+
     fn page_title(&self) -> Result<Vec<String>> {
         if let Ok(doc) = self.parse.document() {
             Ok(doc
@@ -172,14 +368,164 @@ where
             Err(Error::from(ErrorKind::Document))
         }
     }
-}
 
-impl Parse<Utils> {
-    pub fn fix_link(link: &str) -> &str {
-        Utils::fix_link(link)
+    fn rdfa(&self) -> Result<Vec<Item>> {
+        if let Ok(doc) = self.parse.document() {
+            Ok(doc
+                .find(Attr(HtmlAttribute::TypeOf.into(), ()))
+                .filter(|n| {
+                    n.attr(HtmlAttribute::Property.into()).is_none()
+                        || !Utils::within(n, HtmlAttribute::TypeOf)
+                })
+                .map(|n| Item::from_rdfa(&n))
+                .collect())
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
     }
 
+    fn robots(&self) -> Result<Vec<RobotsMeta>> {
+        if let Ok(doc) = self.parse.document() {
+            Ok(doc
+                .find(Name(HtmlTag::Meta.into()))
+                .filter_map(|n| {
+                    let name = n.attr(HtmlAttribute::Name.into())?.trim().to_lowercase();
+                    if !RobotsMeta::NAMES.contains(&name.as_str()) {
+                        return None;
+                    }
+                    let content = n.attr(HtmlAttribute::Content.into())?;
+                    Some(RobotsMeta::new(Some(&name), content))
+                })
+                .collect())
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
+    }
+
+    fn select(&self, selection: &Selection) -> Result<Vec<String>> {
+        selection.run(self.parse.dom())
+    }
+
+    fn social(&self) -> Result<Social> {
+        if let Ok(doc) = self.parse.document() {
+            let mut social = Social::default();
+            for n in doc.find(Name(HtmlTag::Meta.into())) {
+                let property = n
+                    .attr(HtmlAttribute::Property.into())
+                    .or_else(|| n.attr(HtmlAttribute::Name.into()));
+                if let (Some(property), Some(content)) =
+                    (property, n.attr(HtmlAttribute::Content.into()))
+                {
+                    social.insert(property, content);
+                }
+            }
+            social.resolve(Utils::base_url(doc, self.parse.url()).as_ref());
+            Ok(social)
+        } else {
+            Err(Error::from(ErrorKind::Document))
+        }
+    }
+}
+
+impl Parse<Utils> {
     pub fn path_to_string(path: PathBuf) -> String {
         Utils::path_to_string(path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_url(html: &str, url: Option<&str>) -> Option<String> {
+        let url = url.map(|url| Url::parse(url).unwrap());
+        Utils::base_url(&Document::from(html), url.as_ref()).map(String::from)
+    }
+
+    fn resolve(base: Option<&str>, href: &str) -> String {
+        let base = base.map(|base| Url::parse(base).unwrap());
+        Utils::resolve(base.as_ref(), href)
+    }
+
+    #[test]
+    fn base_url_takes_an_absolute_base_href_as_is() {
+        let html = "<base href='https://cdn.test/docs/'>";
+        assert_eq!(
+            base_url(html, Some("http://site.test/a/b.html")).unwrap(),
+            "https://cdn.test/docs/"
+        );
+        assert_eq!(base_url(html, None).unwrap(), "https://cdn.test/docs/");
+    }
+
+    #[test]
+    fn base_url_resolves_a_relative_base_href_against_the_page() {
+        let page = Some("http://site.test/a/b.html");
+        assert_eq!(
+            base_url("<base href=' ../c/ '>", page).unwrap(),
+            "http://site.test/c/"
+        );
+        assert_eq!(
+            base_url("<base href='//cdn.test/'>", page).unwrap(),
+            "http://cdn.test/"
+        );
+        // the first base with an href wins
+        let html = "<base target=_blank><base href=/x/><base href=/y/>";
+        assert_eq!(base_url(html, page).unwrap(), "http://site.test/x/");
+    }
+
+    #[test]
+    fn base_url_falls_back_to_the_page_without_an_href() {
+        let page = Some("http://site.test/a/b.html#top");
+        assert_eq!(
+            base_url("<p>no base</p>", page).unwrap(),
+            "http://site.test/a/b.html#top"
+        );
+        assert_eq!(
+            base_url("<base target=_blank>", page).unwrap(),
+            "http://site.test/a/b.html#top"
+        );
+        assert_eq!(
+            base_url("<base href=''>", page).unwrap(),
+            "http://site.test/a/b.html"
+        );
+    }
+
+    #[test]
+    fn base_url_without_a_page_url_needs_an_absolute_href() {
+        assert_eq!(base_url("<p>no base</p>", None), None);
+        assert_eq!(base_url("<base href=''>", None), None);
+        assert_eq!(base_url("<base href='/docs/'>", None), None);
+    }
+
+    #[test]
+    fn resolve_joins_hrefs_to_the_base() {
+        let base = Some("http://site.test/a/b.html");
+        assert_eq!(resolve(base, "c.html"), "http://site.test/a/c.html");
+        assert_eq!(resolve(base, " /d?e=f "), "http://site.test/d?e=f");
+        assert_eq!(resolve(base, "#top"), "http://site.test/a/b.html#top");
+        assert_eq!(resolve(base, ""), "http://site.test/a/b.html");
+        assert_eq!(resolve(base, "HTTP://Other.test"), "http://other.test/");
+    }
+
+    #[test]
+    fn resolve_keeps_the_scheme_of_the_base_for_protocol_relative_links() {
+        assert_eq!(
+            resolve(Some("https://site.test/"), "//cdn.test/a.js"),
+            "https://cdn.test/a.js"
+        );
+        assert_eq!(
+            resolve(Some("http://site.test/"), "//cdn.test/a.js"),
+            "http://cdn.test/a.js"
+        );
+        assert_eq!(resolve(None, "//cdn.test/a.js"), "//cdn.test/a.js");
+    }
+
+    #[test]
+    fn resolve_without_a_base_leaves_relative_hrefs_as_written() {
+        // documents read from stdin have no url
+        assert_eq!(resolve(None, "about.html"), "about.html");
+        assert_eq!(resolve(None, " #top "), "#top");
+        assert_eq!(resolve(None, "http://site.test"), "http://site.test/");
+        assert_eq!(resolve(None, "mailto:me@site.test"), "mailto:me@site.test");
+    }
+}
@@ -1,8 +1,15 @@
 use select::predicate::Predicate;
 use std::default::Default as StdDefault;
+use tokio::io::AsyncRead;
 
-use crate::html::{Headers, Html, HtmlDocument, HtmlParser, HtmlTag};
-use crate::parse::{Default, FromPath, FromUrl, Parse, Parser};
+use crate::html::{
+    Headers, Heading, Html, HtmlDocument, HtmlLoader, HtmlParser, HtmlTag, Image, Link, RobotsMeta,
+    Selection,
+};
+use crate::http::{Client, ResponseMeta};
+use crate::parse::{Default, FromPath, FromReader, FromUrl, Parse, Parser};
+use crate::schema::Item;
+use crate::social::Social;
 use crate::utils::Result;
 
 const DEFAULT_BUFFER_CAPACITY: usize = 4 * 1024 * 1024; // 4mb
@@ -30,6 +37,12 @@ impl Probe {
     pub fn new() -> Probe { Probe {} }
 }
 
+impl StdDefault for Probe {
+    fn default() -> Self {
+        Probe::new()
+    }
+}
+
 impl Probe {
     pub fn document(self) -> DocumentProbe<Parse<Default>> {
         DocumentProbe {
@@ -63,9 +76,11 @@ where
 impl<T> DocumentProbe<T>
 where
     T: Parser + FromPath + Send,
+    T::Output: Parser,
 {
-    pub async fn from(mut self, path: &str) -> Result<Self> {
-        Ok(Self {
+    /// Reads and parses the document at `path`
+    pub async fn from(mut self, path: &str) -> Result<DocumentProbe<T::Output>> {
+        Ok(DocumentProbe {
             capacity: self.capacity,
             path: path.to_string(),
             parse: self.parse.from(path, self.capacity).await?,
@@ -73,11 +88,29 @@ where
     }
 }
 
+impl<T> DocumentProbe<T>
+where
+    T: Parser + FromReader + Send,
+    T::Output: Parser,
+{
+    /// Parses the document from a reader, such as stdin, instead of the file system
+    pub async fn read<R: AsyncRead + Unpin + Send>(
+        mut self,
+        reader: R,
+    ) -> Result<DocumentProbe<T::Output>> {
+        Ok(DocumentProbe {
+            capacity: self.capacity,
+            path: self.path,
+            parse: self.parse.read(reader, self.capacity).await?,
+        })
+    }
+}
+
 impl DocumentProbe<Parse<Default>> {
-    pub fn html(self) -> DocumentProbe<Parse<Html>> {
+    pub fn html(self) -> DocumentProbe<Parse<HtmlLoader>> {
         DocumentProbe {
             capacity: self.capacity,
-            parse: Parse::new(Html::default()),
+            parse: Parse::new(HtmlLoader::default()),
             path: self.path,
         }
     }
@@ -87,19 +120,35 @@ impl<T> DocumentProbe<Parse<T>>
 where
     T: HtmlDocument,
 {
-    pub fn all_links(&self) -> Result<Vec<String>> {
+    pub fn all_links(&self) -> Result<Vec<Link>> {
         self.parse.all_links()
     }
 
+    pub fn all_hrefs(&self) -> Result<Vec<String>> {
+        self.parse.all_hrefs()
+    }
+
     pub fn all_headers(&self) -> Result<Vec<Headers>> {
         self.parse.all_headers(vec![])
     }
+
+    pub fn headers(&self, tags: &[HtmlTag]) -> Result<Vec<Headers>> {
+        self.parse.headers(tags)
+    }
+
+    pub fn links_within(&self, tags: &[HtmlTag]) -> Result<Vec<Link>> {
+        self.parse.links_within(tags)
+    }
 }
 
 impl<T> HtmlParser for DocumentProbe<T>
 where
     T: Parser + HtmlParser,
 {
+    fn canonicals(&self) -> Result<Vec<String>> {
+        self.parse.canonicals()
+    }
+
     fn descriptions(&self) -> Result<Vec<String>> {
         self.parse.descriptions()
     }
@@ -108,21 +157,59 @@ where
         self.parse.header(header)
     }
 
-    fn links<P: Predicate>(&self, predicate: P) -> Result<Vec<String>> {
+    fn headings(&self) -> Result<Vec<Heading>> {
+        self.parse.headings()
+    }
+
+    fn images(&self) -> Result<Vec<Image>> {
+        self.parse.images()
+    }
+
+    fn json_ld(&self) -> Result<Vec<String>> {
+        self.parse.json_ld()
+    }
+
+    fn links<P: Predicate>(&self, predicate: P) -> Result<Vec<Link>> {
         self.parse.links(predicate)
     }
 
+    fn hrefs<P: Predicate>(&self, predicate: P) -> Result<Vec<String>> {
+        self.parse.hrefs(predicate)
+    }
+
+    fn microdata(&self) -> Result<Vec<Item>> {
+        self.parse.microdata()
+    }
+
     fn page_title(&self) -> Result<Vec<String>> {
         self.parse.page_title()
     }
+
+    fn rdfa(&self) -> Result<Vec<Item>> {
+        self.parse.rdfa()
+    }
+
+    fn robots(&self) -> Result<Vec<RobotsMeta>> {
+        self.parse.robots()
+    }
+
+    fn select(&self, selection: &Selection) -> Result<Vec<String>> {
+        self.parse.select(selection)
+    }
+
+    fn social(&self) -> Result<Social> {
+        self.parse.social()
+    }
 }
 
 impl<T> HttpProbe<T>
 where
     T: Parser + FromUrl + Send,
+    T::Output: Parser,
 {
-    pub async fn from(mut self, url: &str) -> Result<Self> {
-        Ok(Self {
+    /// Fetches and parses the document at `url`
+    pub async fn from(mut self, url: &str) -> Result<HttpProbe<T::Output>> {
+        Ok(HttpProbe {
             url: url.to_string(),
             parse: self.parse.from(url).await?,
         })
@@ -130,18 +217,45 @@ where
 }
 
 impl HttpProbe<Parse<Default>> {
-    pub fn html(self) -> HttpProbe<Parse<Html>> {
+    pub fn html(self) -> HttpProbe<Parse<HtmlLoader>> {
         HttpProbe {
-            parse: Parse::new(Html::default()),
+            parse: Parse::new(HtmlLoader::default()),
             url: self.url,
         }
     }
 }
 
+impl HttpProbe<Parse<HtmlLoader>> {
+    /// Fetches documents with the given client instead of a default one
+    pub fn client(self, client: Client) -> Self {
+        HttpProbe {
+            parse: Parse::new(HtmlLoader::with_client(client)),
+            url: self.url,
+        }
+    }
+}
+
+impl HttpProbe<Parse<Html>> {
+    /// Status code of the response the document was read from
+    pub fn status(&self) -> Option<u16> {
+        self.parse.get_ref().status()
+    }
+
+    /// Status, headers, redirects and timing of the response the document
+    /// was read from
+    pub fn response(&self) -> Option<&ResponseMeta> {
+        self.parse.get_ref().response()
+    }
+}
+
 impl<T> HtmlParser for HttpProbe<T>
 where
     T: Parser + HtmlParser,
 {
+    fn canonicals(&self) -> Result<Vec<String>> {
+        self.parse.canonicals()
+    }
+
     fn descriptions(&self) -> Result<Vec<String>> {
         self.parse.descriptions()
     }
@@ -150,24 +264,72 @@ where
         self.parse.header(header)
     }
 
-    fn links<P: Predicate>(&self, predicate: P) -> Result<Vec<String>> {
+    fn headings(&self) -> Result<Vec<Heading>> {
+        self.parse.headings()
+    }
+
+    fn images(&self) -> Result<Vec<Image>> {
+        self.parse.images()
+    }
+
+    fn json_ld(&self) -> Result<Vec<String>> {
+        self.parse.json_ld()
+    }
+
+    fn links<P: Predicate>(&self, predicate: P) -> Result<Vec<Link>> {
         self.parse.links(predicate)
     }
 
+    fn hrefs<P: Predicate>(&self, predicate: P) -> Result<Vec<String>> {
+        self.parse.hrefs(predicate)
+    }
+
+    fn microdata(&self) -> Result<Vec<Item>> {
+        self.parse.microdata()
+    }
+
     fn page_title(&self) -> Result<Vec<String>> {
         self.parse.page_title()
     }
+
+    fn rdfa(&self) -> Result<Vec<Item>> {
+        self.parse.rdfa()
+    }
+
+    fn robots(&self) -> Result<Vec<RobotsMeta>> {
+        self.parse.robots()
+    }
+
+    fn select(&self, selection: &Selection) -> Result<Vec<String>> {
+        self.parse.select(selection)
+    }
+
+    fn social(&self) -> Result<Social> {
+        self.parse.social()
+    }
 }
 
 impl<T> HttpProbe<Parse<T>>
 where
     T: HtmlDocument,
 {
-    pub fn all_links(&self) -> Result<Vec<String>> {
+    pub fn all_links(&self) -> Result<Vec<Link>> {
         self.parse.all_links()
     }
 
+    pub fn all_hrefs(&self) -> Result<Vec<String>> {
+        self.parse.all_hrefs()
+    }
+
     pub fn all_headers(&self) -> Result<Vec<Headers>> {
         self.parse.all_headers(Vec::new())
     }
+
+    pub fn headers(&self, tags: &[HtmlTag]) -> Result<Vec<Headers>> {
+        self.parse.headers(tags)
+    }
+
+    pub fn links_within(&self, tags: &[HtmlTag]) -> Result<Vec<Link>> {
+        self.parse.links_within(tags)
+    }
 }
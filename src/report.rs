@@ -0,0 +1,326 @@
+use select::predicate::Name;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{Audit, Finding};
+use crate::check::LinkCheck;
+use crate::cli::HtmlQuery;
+use crate::data::Origin;
+use crate::error::{Error, ErrorKind, ErrorType};
+use crate::extract::Extracted;
+use crate::html::{AnchorWithin, Headers, Html, HtmlAttribute, HtmlParser, HtmlTag, Link};
+use crate::http::ResponseMeta;
+use crate::image::ImageReport;
+use crate::index::Indexability;
+use crate::outline::Outline;
+use crate::parse::Parse;
+use crate::probe::HttpProbe;
+use crate::schema::StructuredData;
+use crate::social::SocialPreview;
+use reqwest::Url;
+use crate::utils::Result;
+
+/// Data extracted from a single document
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ProbeData {
+    Findings(Vec<Finding>),
+    LinkChecks(Vec<LinkCheck>),
+    Descriptions(Vec<String>),
+    Headers(Vec<Headers>),
+    Images(ImageReport),
+    Indexability(Indexability),
+    Links(Vec<Link>),
+    Outline(Outline),
+    PageTitle(Vec<String>),
+    Response(ResponseMeta),
+    Matches(Vec<String>),
+    Social(SocialPreview),
+    StructuredData(StructuredData),
+}
+
+/// Results of a query run against a single document
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProbeReport {
+    origin: Origin,
+    source: String,
+    data: Option<ProbeData>,
+    extracted: Vec<Extracted>,
+    error: Option<String>,
+}
+
+/// Everything gathered from a single crawled page
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PageReport {
+    url: String,
+    depth: usize,
+    status: Option<u16>,
+    title: Vec<String>,
+    descriptions: Vec<String>,
+    headers: Vec<Headers>,
+    links: Vec<String>,
+    indexability: Option<Indexability>,
+    extracted: Vec<Extracted>,
+    error: Option<String>,
+}
+
+/// Report produced by any of the system's commands
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Report {
+    Page(PageReport),
+    Probe(ProbeReport),
+}
+
+// === impl ProbeData ===
+
+impl ProbeData {
+    /// Runs the specified query against the parser
+    ///
+    /// `tags` limits headers to the given levels and links to those nested
+    /// within the given tags; an empty slice applies no filter.
+    pub fn collect<P: HtmlParser>(
+        parser: &P,
+        query: &HtmlQuery,
+        tags: &[HtmlTag],
+    ) -> Result<ProbeData> {
+        match query {
+            HtmlQuery::Audit(rules) => Ok(ProbeData::Findings(Audit::new(rules).run(parser)?)),
+            HtmlQuery::Description => Ok(ProbeData::Descriptions(parser.descriptions()?)),
+            HtmlQuery::Headers => {
+                // tags are checked to be headers before any document is probed
+                let levels = if tags.is_empty() { &HtmlTag::HEADERS[..] } else { tags };
+                let headers = levels.iter().map(|tag| parser.header(*tag));
+                Ok(ProbeData::Headers(headers.collect::<Result<Vec<_>>>()?))
+            }
+            // images are only fetched once the document is no longer needed
+            HtmlQuery::Images { .. } => Ok(ProbeData::Images(ImageReport::collect(parser)?)),
+            // without a response only the document itself can rule the page out
+            HtmlQuery::Indexability { bot } => {
+                Ok(ProbeData::Indexability(Indexability::assess(parser, None, None, bot)?))
+            }
+            // links are only gathered here; checking them is up to the caller
+            HtmlQuery::Links | HtmlQuery::CheckLinks { .. } if tags.is_empty() => Ok(
+                ProbeData::Links(parser.links(Name(HtmlAttribute::A.into()))?),
+            ),
+            HtmlQuery::Links | HtmlQuery::CheckLinks { .. } => {
+                Ok(ProbeData::Links(parser.links(AnchorWithin::new(tags))?))
+            }
+            HtmlQuery::Outline => Ok(ProbeData::Outline(Outline::collect(parser)?)),
+            HtmlQuery::PageTitle => Ok(ProbeData::PageTitle(parser.page_title()?)),
+            HtmlQuery::Select(selection) => Ok(ProbeData::Matches(parser.select(selection)?)),
+            HtmlQuery::Social => Ok(ProbeData::Social(SocialPreview::collect(parser)?)),
+            HtmlQuery::StructuredData => {
+                Ok(ProbeData::StructuredData(StructuredData::collect(parser)?))
+            }
+            // only a fetched document has a response; see `ProbeData::response`
+            HtmlQuery::Response => Err(Error::new(ErrorType::SimpleMessage(
+                ErrorKind::InvalidInput,
+                &"only urls have a response",
+            ))),
+        }
+    }
+
+    /// Same as `collect`, answering the queries that need the probe's response
+    ///
+    /// `blocked` is the `robots.txt` rule disallowing the url, if any.
+    pub fn response(
+        probe: &HttpProbe<Parse<Html>>,
+        query: &HtmlQuery,
+        tags: &[HtmlTag],
+        blocked: Option<&str>,
+    ) -> Result<ProbeData> {
+        match (query, probe.response()) {
+            (HtmlQuery::Response, Some(response)) => Ok(ProbeData::Response(response.clone())),
+            (HtmlQuery::Indexability { bot }, response) => Ok(ProbeData::Indexability(
+                Indexability::assess(probe, response, blocked, bot)?,
+            )),
+            _ => ProbeData::collect(probe, query, tags),
+        }
+    }
+
+    /// Problems found in the document; queries looking for none have none
+    pub fn findings(&self) -> &[Finding] {
+        match self {
+            ProbeData::Findings(findings) => findings,
+            ProbeData::Images(report) => report.findings(),
+            ProbeData::Outline(outline) => outline.findings(),
+            ProbeData::Social(preview) => preview.findings(),
+            ProbeData::StructuredData(data) => data.findings(),
+            _ => &[],
+        }
+    }
+}
+
+// === impl ProbeReport ===
+
+impl ProbeReport {
+    pub fn new(origin: Origin, source: &str, data: ProbeData) -> ProbeReport {
+        ProbeReport {
+            origin,
+            source: source.to_string(),
+            data: Some(data),
+            extracted: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Creates a report for a document that could not be probed
+    pub fn failed(origin: Origin, source: &str, error: &Error) -> ProbeReport {
+        ProbeReport {
+            origin,
+            source: source.to_string(),
+            data: None,
+            extracted: Vec::new(),
+            error: Some(error.to_string()),
+        }
+    }
+
+    /// Attaches the values extraction rules pulled out of the document
+    pub fn with_extracted(mut self, extracted: Vec<Extracted>) -> Self {
+        self.extracted = extracted;
+        self
+    }
+
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn data(&self) -> Option<&ProbeData> {
+        self.data.as_ref()
+    }
+
+    pub fn extracted(&self) -> &[Extracted] {
+        &self.extracted
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+// === impl PageReport ===
+
+impl PageReport {
+    /// Gathers the page's metadata; only http(s) links are kept, without
+    /// their fragment
+    pub fn collect<P: HtmlParser>(
+        parser: &P,
+        url: &Url,
+        depth: usize,
+        status: Option<u16>,
+    ) -> Result<PageReport> {
+        let mut links: Vec<String> = Vec::new();
+        for link in parser.links(Name(HtmlAttribute::A.into()))? {
+            let mut link = match Url::parse(link.url()) {
+                Ok(link) if matches!(link.scheme(), "http" | "https") => link,
+                _ => continue,
+            };
+            link.set_fragment(None);
+            if !links.iter().any(|l| l == link.as_str()) {
+                links.push(link.into());
+            }
+        }
+
+        Ok(PageReport {
+            url: url.to_string(),
+            depth,
+            status,
+            title: parser.page_title()?,
+            descriptions: parser.descriptions()?,
+            headers: HtmlTag::HEADERS
+                .iter()
+                .map(|tag| parser.header(*tag))
+                .collect::<Result<Vec<_>>>()?,
+            links,
+            indexability: None,
+            extracted: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Creates a report for a page that was never fetched, as robots.txt
+    /// disallows it
+    pub fn blocked(url: &Url, depth: usize) -> PageReport {
+        PageReport {
+            url: url.to_string(),
+            depth,
+            status: None,
+            title: Vec::new(),
+            descriptions: Vec::new(),
+            headers: Vec::new(),
+            links: Vec::new(),
+            indexability: None,
+            extracted: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Creates a report for a page that could not be fetched
+    pub fn failed(url: &Url, depth: usize, error: &Error) -> PageReport {
+        PageReport {
+            error: Some(error.to_string()),
+            ..PageReport::blocked(url, depth)
+        }
+    }
+
+    /// Attaches the values extraction rules pulled out of the page
+    pub fn with_extracted(mut self, extracted: Vec<Extracted>) -> Self {
+        self.extracted = extracted;
+        self
+    }
+
+    pub fn with_indexability(mut self, indexability: Indexability) -> Self {
+        self.indexability = Some(indexability);
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    pub fn title(&self) -> &[String] {
+        &self.title
+    }
+
+    pub fn descriptions(&self) -> &[String] {
+        &self.descriptions
+    }
+
+    pub fn headers(&self) -> &[Headers] {
+        &self.headers
+    }
+
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+
+    pub fn indexability(&self) -> Option<&Indexability> {
+        self.indexability.as_ref()
+    }
+
+    pub fn extracted(&self) -> &[Extracted] {
+        &self.extracted
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
@@ -0,0 +1,643 @@
+use std::fmt::{Display, Formatter};
+
+use select::node::Node;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::audit::{Finding, Severity};
+use crate::html::{HtmlAttribute, HtmlParser};
+use crate::utils::Result;
+
+/// Properties a rich result type needs, and those that improve it
+///
+/// A required entry such as `offers|review|aggregateRating` is met by any one
+/// of its alternatives.
+struct TypeRules {
+    types: &'static [&'static str],
+    required: &'static [&'static str],
+    recommended: &'static [&'static str],
+}
+
+/// Rules for the rich result types most audits care about, and the items
+/// nested within them
+const RULES: [TypeRules; 10] = [
+    TypeRules {
+        types: &["Article", "NewsArticle", "BlogPosting"],
+        required: &["headline"],
+        recommended: &["image", "author", "datePublished", "dateModified"],
+    },
+    TypeRules {
+        types: &["Product"],
+        required: &["name", "offers|review|aggregateRating"],
+        recommended: &["image", "description", "brand", "sku"],
+    },
+    TypeRules {
+        types: &["Offer"],
+        required: &["price|priceSpecification"],
+        recommended: &["priceCurrency", "availability", "url"],
+    },
+    TypeRules {
+        types: &["BreadcrumbList"],
+        required: &["itemListElement"],
+        recommended: &[],
+    },
+    TypeRules {
+        types: &["ListItem"],
+        // breadcrumbs often name the page within `item` instead
+        required: &["position", "name|item"],
+        recommended: &["item"],
+    },
+    TypeRules {
+        types: &["FAQPage"],
+        required: &["mainEntity"],
+        recommended: &[],
+    },
+    TypeRules {
+        types: &["Question"],
+        required: &["name", "acceptedAnswer"],
+        recommended: &[],
+    },
+    TypeRules {
+        types: &["Answer"],
+        required: &["text"],
+        recommended: &[],
+    },
+    TypeRules {
+        types: &["Organization", "Corporation"],
+        required: &["name"],
+        recommended: &["url", "logo", "sameAs", "contactPoint"],
+    },
+    TypeRules {
+        types: &["LocalBusiness", "Restaurant", "Store"],
+        required: &["name", "address"],
+        recommended: &["telephone", "openingHoursSpecification", "geo", "url", "image"],
+    },
+];
+
+/// Attribute holding the value of a microdata property, by element; any
+/// other element's value is its text
+const MICRODATA_VALUES: [(&str, &str); 15] = [
+    ("a", "href"),
+    ("area", "href"),
+    ("audio", "src"),
+    ("data", "value"),
+    ("embed", "src"),
+    ("iframe", "src"),
+    ("img", "src"),
+    ("link", "href"),
+    ("meta", "content"),
+    ("meter", "value"),
+    ("object", "data"),
+    ("source", "src"),
+    ("time", "datetime"),
+    ("track", "src"),
+    ("video", "src"),
+];
+
+/// Markup structured data was written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Syntax {
+    JsonLd,
+    Microdata,
+    Rdfa,
+}
+
+/// Structured data item, such as a schema.org `Product`, with its properties
+/// in the order written
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Item {
+    syntax: Syntax,
+    /// Schema.org types without their vocabulary, such as `Product`
+    types: Vec<String>,
+    id: Option<String>,
+    properties: Vec<(String, ItemValue)>,
+}
+
+/// Value of a single property; a property with several values appears once
+/// for each
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ItemValue {
+    Text(String),
+    Item(Item),
+}
+
+/// Every item in a document along with the problems found in them
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StructuredData {
+    items: Vec<Item>,
+    findings: Vec<Finding>,
+}
+
+// === impl TypeRules ===
+
+impl TypeRules {
+    fn applies(&self, item: &Item) -> bool {
+        item.types().iter().any(|kind| self.types.contains(&kind.as_str()))
+    }
+}
+
+// === impl Syntax ===
+
+impl Display for Syntax {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Syntax::JsonLd => write!(f, "json-ld"),
+            Syntax::Microdata => write!(f, "microdata"),
+            Syntax::Rdfa => write!(f, "rdfa"),
+        }
+    }
+}
+
+// === impl Item ===
+
+impl Item {
+    /// Top level items of a JSON-LD block; arrays and `@graph` are flattened
+    pub fn from_json_ld(value: &Value) -> Vec<Item> {
+        match value {
+            Value::Array(values) => values.iter().flat_map(Item::from_json_ld).collect(),
+            Value::Object(object) => match object.get("@graph") {
+                Some(graph) => Item::from_json_ld(graph),
+                None => vec![Item::json_object(object)],
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    /// Item declared by an element with `itemscope`
+    ///
+    /// Properties are read from the element's descendants, stopping at nested
+    /// items, which hold their own; `itemref` is not followed.
+    pub fn from_microdata(node: &Node) -> Item {
+        let mut properties = Vec::new();
+        Item::microdata_properties(node, &mut properties);
+        Item {
+            syntax: Syntax::Microdata,
+            types: Item::attr_types(node, HtmlAttribute::ItemType),
+            id: node.attr(HtmlAttribute::ItemId.into()).map(str::to_string),
+            properties,
+        }
+    }
+
+    /// Item declared by an element with `typeof`
+    ///
+    /// Properties are read from the element's descendants, stopping at nested
+    /// items, which hold their own.
+    pub fn from_rdfa(node: &Node) -> Item {
+        let mut properties = Vec::new();
+        Item::rdfa_properties(node, &mut properties);
+        Item {
+            syntax: Syntax::Rdfa,
+            types: Item::attr_types(node, HtmlAttribute::TypeOf),
+            id: node.attr(HtmlAttribute::Resource.into()).map(str::to_string),
+            properties,
+        }
+    }
+
+    pub fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+
+    pub fn types(&self) -> &[String] {
+        &self.types
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    pub fn properties(&self) -> &[(String, ItemValue)] {
+        &self.properties
+    }
+
+    /// True when the property has at least one non empty value
+    pub fn has(&self, name: &str) -> bool {
+        self.properties.iter().any(|(n, value)| {
+            n == name
+                && match value {
+                    ItemValue::Text(text) => !text.trim().is_empty(),
+                    ItemValue::Item(_) => true,
+                }
+        })
+    }
+
+    /// Strips the vocabulary from types such as `https://schema.org/Product`
+    /// or `schema:Product`
+    fn short_type(kind: &str) -> String {
+        let kind = kind.trim();
+        kind.rsplit(['/', '#', ':']).next().unwrap_or(kind).to_string()
+    }
+
+    fn attr_types(node: &Node, attr: HtmlAttribute) -> Vec<String> {
+        node.attr(attr.into())
+            .map(|types| types.split_whitespace().map(Item::short_type).collect())
+            .unwrap_or_default()
+    }
+
+    fn microdata_properties(node: &Node, properties: &mut Vec<(String, ItemValue)>) {
+        for child in node.children() {
+            let scope = child.attr(HtmlAttribute::ItemScope.into()).is_some();
+            if let Some(names) = child.attr(HtmlAttribute::ItemProp.into()) {
+                let value = match scope {
+                    true => ItemValue::Item(Item::from_microdata(&child)),
+                    false => {
+                        let attr = MICRODATA_VALUES
+                            .iter()
+                            .find(|(name, _)| child.name() == Some(name))
+                            .and_then(|(_, attr)| child.attr(attr));
+                        ItemValue::Text(attr.map_or_else(|| Item::text(&child), str::to_string))
+                    }
+                };
+                for name in names.split_whitespace() {
+                    properties.push((Item::short_type(name), value.clone()));
+                }
+            }
+            if !scope {
+                Item::microdata_properties(&child, properties);
+            }
+        }
+    }
+
+    fn rdfa_properties(node: &Node, properties: &mut Vec<(String, ItemValue)>) {
+        for child in node.children() {
+            let scope = child.attr(HtmlAttribute::TypeOf.into()).is_some();
+            if let Some(names) = child.attr(HtmlAttribute::Property.into()) {
+                let value = match scope {
+                    true => ItemValue::Item(Item::from_rdfa(&child)),
+                    // `content` overrides everything, then the resource linked to
+                    false => {
+                        let attr = [
+                            HtmlAttribute::Content,
+                            HtmlAttribute::Resource,
+                            HtmlAttribute::Href,
+                            HtmlAttribute::Src,
+                        ]
+                        .into_iter()
+                        .find_map(|attr| child.attr(attr.into()));
+                        ItemValue::Text(attr.map_or_else(|| Item::text(&child), str::to_string))
+                    }
+                };
+                for name in names.split_whitespace() {
+                    properties.push((Item::short_type(name), value.clone()));
+                }
+            }
+            if !scope {
+                Item::rdfa_properties(&child, properties);
+            }
+        }
+    }
+
+    fn text(node: &Node) -> String {
+        node.text().split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn json_object(object: &Map<String, Value>) -> Item {
+        let types = match object.get("@type") {
+            Some(Value::String(kind)) => vec![Item::short_type(kind)],
+            Some(Value::Array(kinds)) => {
+                kinds.iter().filter_map(Value::as_str).map(Item::short_type).collect()
+            }
+            _ => Vec::new(),
+        };
+        let mut properties = Vec::new();
+        for (name, value) in object.iter().filter(|(name, _)| !name.starts_with('@')) {
+            let values = match value {
+                Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            };
+            for value in values {
+                if let Some(value) = Item::json_value(value) {
+                    properties.push((name.clone(), value));
+                }
+            }
+        }
+        Item {
+            syntax: Syntax::JsonLd,
+            types,
+            id: object.get("@id").and_then(Value::as_str).map(str::to_string),
+            properties,
+        }
+    }
+
+    fn json_value(value: &Value) -> Option<ItemValue> {
+        match value {
+            Value::Null | Value::Array(_) => None,
+            Value::String(text) => Some(ItemValue::Text(text.clone())),
+            Value::Bool(_) | Value::Number(_) => Some(ItemValue::Text(value.to_string())),
+            // value objects such as `{"@value": "..", "@language": "en"}`
+            Value::Object(object) => match object.get("@value") {
+                Some(Value::String(text)) => Some(ItemValue::Text(text.clone())),
+                Some(value) => Some(ItemValue::Text(value.to_string())),
+                None => Some(ItemValue::Item(Item::json_object(object))),
+            },
+        }
+    }
+}
+
+// === impl StructuredData ===
+
+impl StructuredData {
+    /// Reads every item in the document and checks each one, nested items
+    /// included, against the bundled rules
+    pub fn collect<P: HtmlParser>(parser: &P) -> Result<StructuredData> {
+        let mut items = Vec::new();
+        let mut findings = Vec::new();
+        for (i, block) in parser.json_ld()?.iter().enumerate() {
+            match serde_json::from_str::<Value>(block) {
+                Ok(value) => items.extend(Item::from_json_ld(&value)),
+                Err(err) => findings.push(Finding::new(
+                    "invalid-json-ld",
+                    Severity::Error,
+                    format!("json-ld block {} is not valid json; {}", i + 1, err),
+                )),
+            }
+        }
+
+        items.extend(parser.microdata()?);
+        items.extend(parser.rdfa()?);
+
+        for item in &items {
+            StructuredData::check(item, "", &mut findings);
+        }
+        Ok(StructuredData { items, findings })
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Checks `item` and everything nested within it; `path` leads to the
+    /// item from the top level one, such as `BreadcrumbList.itemListElement`
+    fn check(item: &Item, path: &str, findings: &mut Vec<Finding>) {
+        let name = match item.types().first() {
+            Some(kind) if path.is_empty() => kind.clone(),
+            Some(kind) => format!("{} ({})", path, kind),
+            None if path.is_empty() => "item".to_string(),
+            None => path.to_string(),
+        };
+        if item.types().is_empty() && path.is_empty() {
+            findings.push(Finding::new(
+                "schema-missing-type",
+                Severity::Warning,
+                format!("{} has no @type", name),
+            ));
+        }
+
+        for rules in RULES.iter().filter(|rules| rules.applies(item)) {
+            for required in rules.required {
+                if !required.split('|').any(|property| item.has(property)) {
+                    let required = required.replace('|', " or ");
+                    findings.push(Finding::new(
+                        "schema-missing-required",
+                        Severity::Error,
+                        format!("{} is missing required property {}", name, required),
+                    ));
+                }
+            }
+            for recommended in rules.recommended.iter().filter(|p| !item.has(p)) {
+                findings.push(Finding::new(
+                    "schema-missing-recommended",
+                    Severity::Warning,
+                    format!("{} is missing recommended property {}", name, recommended),
+                ));
+            }
+        }
+
+        let root = match item.types().first() {
+            Some(kind) if path.is_empty() => kind.as_str(),
+            None if path.is_empty() => "item",
+            _ => path,
+        };
+        for (property, value) in item.properties() {
+            if let ItemValue::Item(nested) = value {
+                StructuredData::check(nested, &format!("{}.{}", root, property), findings);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::Html;
+
+    fn collect(html: &str) -> StructuredData {
+        StructuredData::collect(&Html::inline(html, None)).unwrap()
+    }
+
+    fn json_ld(blocks: &[&str]) -> StructuredData {
+        let scripts = blocks
+            .iter()
+            .map(|block| format!("<script type='application/ld+json'>{}</script>", block))
+            .collect::<String>();
+        collect(&scripts)
+    }
+
+    fn types(data: &StructuredData) -> Vec<&str> {
+        data.items().iter().flat_map(Item::types).map(String::as_str).collect()
+    }
+
+    fn messages<'a>(data: &'a StructuredData, rule: &str) -> Vec<&'a str> {
+        data.findings().iter().filter(|f| f.rule() == rule).map(Finding::message).collect()
+    }
+
+    fn texts<'a>(item: &'a Item, name: &str) -> Vec<&'a str> {
+        item.properties()
+            .iter()
+            .filter_map(|(n, value)| match value {
+                ItemValue::Text(text) if n == name => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn nested<'a>(item: &'a Item, name: &str) -> &'a Item {
+        item.properties()
+            .iter()
+            .find_map(|(n, value)| match value {
+                ItemValue::Item(item) if n == name => Some(item),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn json_ld_graphs_and_arrays_are_flattened() {
+        let data = json_ld(&[
+            r#"{"@context": "https://schema.org", "@graph": [
+                {"@type": "WebSite", "name": "Site"},
+                {"@type": ["Organization", "Corporation"], "name": "Org"}
+            ]}"#,
+            r#"[{"@type": "schema:Person", "name": "Me"}, [{"@type": "Thing"}]]"#,
+        ]);
+
+        assert_eq!(types(&data), ["WebSite", "Organization", "Corporation", "Person", "Thing"]);
+        assert!(data.items().iter().all(|item| item.syntax() == Syntax::JsonLd));
+        assert!(messages(&data, "schema-missing-required").is_empty());
+    }
+
+    #[test]
+    fn malformed_json_ld_is_a_finding() {
+        let data = json_ld(&[r#"{"@type": "Thing"}"#, r#"{"@type": "Thing",}"#]);
+
+        assert_eq!(types(&data), ["Thing"]);
+        let invalid = data.findings().iter().find(|f| f.rule() == "invalid-json-ld").unwrap();
+        assert_eq!(invalid.severity(), Severity::Error);
+        assert!(invalid.message().starts_with("json-ld block 2 is not valid json"));
+    }
+
+    #[test]
+    fn breadcrumbs_may_name_their_items_through_the_item() {
+        let data = json_ld(&[r#"{"@type": "BreadcrumbList", "itemListElement": [
+            {"@type": "ListItem", "position": 1, "name": "Home", "item": "https://site.test/"},
+            {"@type": "ListItem", "position": 2,
+             "item": {"@id": "https://site.test/docs", "name": "Docs"}},
+            {"@type": "ListItem", "position": 3}
+        ]}"#]);
+
+        assert_eq!(
+            messages(&data, "schema-missing-required"),
+            ["BreadcrumbList.itemListElement (ListItem) is missing required property name or item"]
+        );
+    }
+
+    #[test]
+    fn each_rich_result_type_checks_its_required_properties() {
+        let cases = [
+            (r#"{"@type": "BlogPosting"}"#, "BlogPosting is missing required property headline"),
+            (
+                r#"{"@type": "Product", "name": "Shoe"}"#,
+                "Product is missing required property offers or review or aggregateRating",
+            ),
+            (
+                r#"{"@type": "Offer", "priceCurrency": "EUR"}"#,
+                "Offer is missing required property price or priceSpecification",
+            ),
+            (
+                r#"{"@type": "BreadcrumbList"}"#,
+                "BreadcrumbList is missing required property itemListElement",
+            ),
+            (
+                r#"{"@type": "ListItem", "name": "Home"}"#,
+                "ListItem is missing required property position",
+            ),
+            (r#"{"@type": "FAQPage"}"#, "FAQPage is missing required property mainEntity"),
+            (
+                r#"{"@type": "Question", "name": "Why?"}"#,
+                "Question is missing required property acceptedAnswer",
+            ),
+            (r#"{"@type": "Answer", "text": " "}"#, "Answer is missing required property text"),
+            (r#"{"@type": "Corporation"}"#, "Corporation is missing required property name"),
+            (
+                r#"{"@type": "Restaurant", "name": "Diner"}"#,
+                "Restaurant is missing required property address",
+            ),
+        ];
+        assert_eq!(cases.len(), RULES.len());
+
+        for (block, message) in cases {
+            let data = json_ld(&[block]);
+            assert_eq!(messages(&data, "schema-missing-required"), [message], "{}", block);
+        }
+    }
+
+    #[test]
+    fn nested_items_are_checked_along_their_path() {
+        let data = json_ld(&[r#"{"@type": "FAQPage", "mainEntity": {
+            "@type": "Question", "name": "Why?", "acceptedAnswer": {"@type": "Answer"}
+        }}"#]);
+
+        assert_eq!(
+            messages(&data, "schema-missing-required"),
+            ["FAQPage.mainEntity.acceptedAnswer (Answer) is missing required property text"]
+        );
+    }
+
+    #[test]
+    fn microdata_items_hold_their_own_properties() {
+        let data = collect(
+            "<div itemscope itemtype='https://schema.org/Product' itemid='#shoe'>
+               <h1 itemprop='name'>Running   shoe</h1>
+               <img itemprop='image' src='/shoe.png'>
+               <div itemprop='offers' itemscope itemtype='https://schema.org/Offer'>
+                 <meta itemprop='price' content='50'>
+                 <span itemprop='priceCurrency'>EUR</span>
+                 <div itemprop='seller' itemscope itemtype='https://schema.org/Organization'>
+                   <span itemprop='name'>Shop</span>
+                 </div>
+               </div>
+               <p><span itemprop='brand description'>Acme</span></p>
+             </div>",
+        );
+
+        assert_eq!(data.items().len(), 1);
+        let product = &data.items()[0];
+        assert_eq!(product.syntax(), Syntax::Microdata);
+        assert_eq!(product.types(), ["Product"]);
+        assert_eq!(product.id(), Some("#shoe"));
+        assert_eq!(texts(product, "name"), ["Running shoe"]);
+        assert_eq!(texts(product, "image"), ["/shoe.png"]);
+        assert_eq!(texts(product, "brand"), ["Acme"]);
+        assert_eq!(texts(product, "description"), ["Acme"]);
+
+        // nested items keep their properties to themselves
+        let offer = nested(product, "offers");
+        assert_eq!(offer.types(), ["Offer"]);
+        assert_eq!(texts(offer, "price"), ["50"]);
+        assert!(texts(product, "price").is_empty());
+        assert_eq!(texts(nested(offer, "seller"), "name"), ["Shop"]);
+        assert!(texts(offer, "name").is_empty());
+
+        assert_eq!(
+            messages(&data, "schema-missing-recommended"),
+            [
+                "Product is missing recommended property sku",
+                "Product.offers (Offer) is missing recommended property availability",
+                "Product.offers (Offer) is missing recommended property url",
+                "Product.offers.seller (Organization) is missing recommended property url",
+                "Product.offers.seller (Organization) is missing recommended property logo",
+                "Product.offers.seller (Organization) is missing recommended property sameAs",
+                "Product.offers.seller (Organization) is missing recommended property contactPoint",
+            ]
+        );
+    }
+
+    #[test]
+    fn rdfa_items_read_content_before_resources_and_text() {
+        let data = collect(
+            "<div vocab='https://schema.org/' typeof='Article' resource='#post'>
+               <h1 property='headline'>Hello</h1>
+               <span property='datePublished' content='2024-01-01'>January 1st</span>
+               <a property='image' href='/a.png' resource='/b.png'>cover</a>
+               <div property='author' typeof='schema:Person'>
+                 <span property='name'>Me</span>
+               </div>
+             </div>
+             <p typeof='Thing'></p>",
+        );
+
+        assert_eq!(types(&data), ["Article", "Thing"]);
+        let article = &data.items()[0];
+        assert_eq!(article.syntax(), Syntax::Rdfa);
+        assert_eq!(article.id(), Some("#post"));
+        assert_eq!(texts(article, "headline"), ["Hello"]);
+        assert_eq!(texts(article, "datePublished"), ["2024-01-01"]);
+        assert_eq!(texts(article, "image"), ["/b.png"]);
+
+        let author = nested(article, "author");
+        assert_eq!(author.types(), ["Person"]);
+        assert_eq!(texts(author, "name"), ["Me"]);
+        assert!(texts(article, "name").is_empty());
+
+        assert!(messages(&data, "schema-missing-required").is_empty());
+        assert_eq!(
+            messages(&data, "schema-missing-recommended"),
+            ["Article is missing recommended property dateModified"]
+        );
+    }
+}
@@ -33,6 +33,10 @@ impl<B> Request<B> {
         Self { body }
     }
 
+    pub fn body(&self) -> &B {
+        &self.body
+    }
+
     pub fn into_body(self) -> B {
         self.body
     }
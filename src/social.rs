@@ -0,0 +1,352 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{Finding, Severity};
+use crate::html::HtmlParser;
+use crate::utils::Result;
+
+/// Values of `twitter:card` the platform renders
+pub const TWITTER_CARDS: [&str; 4] = ["summary", "summary_large_image", "app", "player"];
+
+/// Properties of a single social card; only the first value of each is kept
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Card {
+    title: Option<String>,
+    description: Option<String>,
+    image: Option<String>,
+    /// `image` resolved against the document's base url
+    image_url: Option<String>,
+    url: Option<String>,
+    /// `og:type` or `twitter:card`
+    kind: Option<String>,
+}
+
+/// Open Graph and Twitter Card properties of a document
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Social {
+    open_graph: Card,
+    twitter: Card,
+}
+
+/// Social cards along with the problems found in them
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SocialPreview {
+    social: Social,
+    findings: Vec<Finding>,
+}
+
+// === impl Card ===
+
+impl Card {
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Image url exactly as written
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    /// Image url resolved against the document's base url; `None` when it is
+    /// relative and there is nothing to resolve it against
+    pub fn image_url(&self) -> Option<&str> {
+        self.image_url.as_deref()
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn kind(&self) -> Option<&str> {
+        self.kind.as_deref()
+    }
+
+    /// This card, taking the title, description, image and url of `fallback`
+    /// for any it lacks; the type or card is never taken
+    fn or(&self, fallback: &Card) -> Card {
+        let image = if self.image.is_some() { self } else { fallback };
+        Card {
+            title: self.title.clone().or_else(|| fallback.title.clone()),
+            description: self.description.clone().or_else(|| fallback.description.clone()),
+            image: image.image.clone(),
+            image_url: image.image_url.clone(),
+            url: self.url.clone().or_else(|| fallback.url.clone()),
+            kind: self.kind.clone(),
+        }
+    }
+
+    fn set(field: &mut Option<String>, content: &str) {
+        let content = content.trim();
+        if field.is_none() && !content.is_empty() {
+            *field = Some(content.to_string());
+        }
+    }
+}
+
+// === impl Social ===
+
+impl Social {
+    /// Records the `content` of a meta tag; properties other than the title,
+    /// description, image, url and type or card of either platform are ignored
+    pub fn insert(&mut self, property: &str, content: &str) {
+        let property = property.trim().to_lowercase();
+        let (card, name) = match property.split_once(':') {
+            Some(("og", name)) => (&mut self.open_graph, name),
+            Some(("twitter", name)) => (&mut self.twitter, name),
+            _ => return,
+        };
+        let field = match name {
+            "title" => &mut card.title,
+            "description" => &mut card.description,
+            "image" | "image:url" | "image:src" => &mut card.image,
+            "url" => &mut card.url,
+            "type" | "card" => &mut card.kind,
+            _ => return,
+        };
+        Card::set(field, content);
+    }
+
+    /// Resolves the image url of both cards against `base`
+    pub fn resolve(&mut self, base: Option<&Url>) {
+        for card in [&mut self.open_graph, &mut self.twitter] {
+            let image = card.image.as_deref();
+            card.image_url = match base {
+                Some(base) => image.and_then(|image| base.join(image).ok()),
+                None => image.and_then(|image| Url::parse(image).ok()),
+            }
+            .map(String::from);
+        }
+    }
+
+    pub fn open_graph(&self) -> &Card {
+        &self.open_graph
+    }
+
+    pub fn twitter(&self) -> &Card {
+        &self.twitter
+    }
+
+    /// Every property found, as `(property, value)`, Open Graph first
+    pub fn properties(&self) -> Vec<(&'static str, &str)> {
+        let og = &self.open_graph;
+        let twitter = &self.twitter;
+        [
+            ("og:title", og.title()),
+            ("og:description", og.description()),
+            ("og:image", og.image()),
+            ("og:url", og.url()),
+            ("og:type", og.kind()),
+            ("twitter:card", twitter.kind()),
+            ("twitter:title", twitter.title()),
+            ("twitter:description", twitter.description()),
+            ("twitter:image", twitter.image()),
+            ("twitter:url", twitter.url()),
+        ]
+        .into_iter()
+        .filter_map(|(property, value)| Some((property, value?)))
+        .collect()
+    }
+}
+
+// === impl SocialPreview ===
+
+impl SocialPreview {
+    /// Reads the document's social cards and checks them for missing required
+    /// properties, relative urls and disagreement with the title or canonical
+    pub fn collect<P: HtmlParser>(parser: &P) -> Result<SocialPreview> {
+        let social = parser.social()?;
+        let og = social.open_graph();
+        let mut findings = Vec::new();
+
+        // required by the Open Graph protocol
+        let required = [
+            ("og:title", og.title()),
+            ("og:type", og.kind()),
+            ("og:image", og.image()),
+            ("og:url", og.url()),
+        ];
+        for (property, value) in required {
+            if value.is_none() {
+                findings.push(SocialPreview::missing(property, Severity::Warning));
+            }
+        }
+        if og.description().is_none() {
+            findings.push(SocialPreview::missing("og:description", Severity::Info));
+        }
+        match social.twitter().kind() {
+            None => findings.push(SocialPreview::missing("twitter:card", Severity::Warning)),
+            Some(card) if !TWITTER_CARDS.contains(&card) => findings.push(Finding::new(
+                "invalid-twitter-card",
+                Severity::Warning,
+                format!("twitter:card {} is not one of {}", card, TWITTER_CARDS.join(", ")),
+            )),
+            Some(_) => {}
+        }
+
+        // platforms fetch these on their own, with nothing to resolve them against
+        let absolute = [
+            ("og:image", og.image(), og.image_url()),
+            ("og:url", og.url(), None),
+            ("twitter:image", social.twitter().image(), social.twitter().image_url()),
+        ];
+        for (property, value, resolved) in absolute {
+            if let Some(value) = value.filter(|v| Url::parse(v).is_err()) {
+                let message = match resolved {
+                    Some(resolved) => format!(
+                        "{} {} is not an absolute url; platforms will not resolve it to {}",
+                        property, value, resolved
+                    ),
+                    None => format!("{} {} is not an absolute url", property, value),
+                };
+                findings.push(Finding::new(
+                    &format!("relative-{}", property.replace(':', "-")),
+                    Severity::Error,
+                    message,
+                ));
+            }
+        }
+
+        let title = parser.page_title()?.into_iter().map(|t| t.trim().to_string()).next();
+        if let (Some(og_title), Some(title)) = (og.title(), title) {
+            if og_title != title {
+                findings.push(Finding::new(
+                    "og-title-mismatch",
+                    Severity::Info,
+                    format!("og:title {} differs from the page title {}", og_title, title),
+                ));
+            }
+        }
+        let canonical = parser.canonicals()?.into_iter().next();
+        if let (Some(og_url), Some(canonical)) = (og.url(), canonical) {
+            if !SocialPreview::same_url(og_url, &canonical) {
+                findings.push(Finding::new(
+                    "og-url-mismatch",
+                    Severity::Warning,
+                    format!("og:url {} differs from the canonical url {}", og_url, canonical),
+                ));
+            }
+        }
+
+        Ok(SocialPreview { social, findings })
+    }
+
+    pub fn social(&self) -> &Social {
+        &self.social
+    }
+
+    /// Card as Twitter renders it; any title, description, image or url
+    /// missing from the Twitter properties is taken from Open Graph
+    pub fn twitter(&self) -> Card {
+        self.social.twitter().or(self.social.open_graph())
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    fn missing(property: &str, severity: Severity) -> Finding {
+        Finding::new(
+            &format!("missing-{}", property.replace(':', "-")),
+            severity,
+            format!("page has no {}", property),
+        )
+    }
+
+    /// Compares urls without their fragment; relative urls only match exactly
+    fn same_url(a: &str, b: &str) -> bool {
+        match (Url::parse(a), Url::parse(b)) {
+            (Ok(mut a), Ok(mut b)) => {
+                a.set_fragment(None);
+                b.set_fragment(None);
+                a == b
+            }
+            _ => a == b,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::Html;
+
+    fn collect(html: &str, url: Option<&str>) -> SocialPreview {
+        SocialPreview::collect(&Html::inline(html, url)).unwrap()
+    }
+
+    fn messages<'a>(preview: &'a SocialPreview, rule: &str) -> Vec<&'a str> {
+        preview.findings().iter().filter(|f| f.rule() == rule).map(Finding::message).collect()
+    }
+
+    #[test]
+    fn twitter_falls_back_to_open_graph() {
+        let preview = collect(
+            "<meta property=og:title content='Open Graph title'>
+             <meta property=og:description content='Open Graph description'>
+             <meta property=og:image content='https://site.test/og.png'>
+             <meta property=og:type content=article>
+             <meta name=twitter:card content=summary>
+             <meta name=twitter:title content='Twitter title'>",
+            None,
+        );
+
+        let twitter = preview.twitter();
+        assert_eq!(twitter.title(), Some("Twitter title"));
+        assert_eq!(twitter.description(), Some("Open Graph description"));
+        assert_eq!(twitter.image(), Some("https://site.test/og.png"));
+        assert_eq!(twitter.kind(), Some("summary"));
+
+        // properties are still listed as written
+        let written = preview.social().twitter();
+        assert_eq!(written.description(), None);
+        assert_eq!(written.image(), None);
+    }
+
+    #[test]
+    fn the_first_value_of_a_property_and_its_aliases_wins() {
+        let preview = collect(
+            "<meta property=og:title content=' '>
+             <meta property=og:title content=First>
+             <meta property=og:title content=Second>
+             <meta property=og:image:url content='https://site.test/a.png'>
+             <meta property=og:image content='https://site.test/b.png'>
+             <meta name=twitter:image:src content='https://site.test/c.png'>",
+            None,
+        );
+
+        assert_eq!(preview.social().open_graph().title(), Some("First"));
+        assert_eq!(preview.social().open_graph().image(), Some("https://site.test/a.png"));
+        assert_eq!(preview.twitter().title(), Some("First"));
+        assert_eq!(preview.twitter().image(), Some("https://site.test/c.png"));
+    }
+
+    #[test]
+    fn relative_images_are_resolved_against_the_page() {
+        let html = "<meta property=og:image content='../img/cover.png'>";
+        let preview = collect(html, Some("http://site.test/blog/post.html"));
+
+        let og = preview.social().open_graph();
+        assert_eq!(og.image(), Some("../img/cover.png"));
+        assert_eq!(og.image_url(), Some("http://site.test/img/cover.png"));
+        assert_eq!(preview.twitter().image_url(), Some("http://site.test/img/cover.png"));
+        assert_eq!(
+            messages(&preview, "relative-og-image"),
+            [
+                "og:image ../img/cover.png is not an absolute url; platforms will not resolve it \
+                 to http://site.test/img/cover.png"
+            ]
+        );
+
+        // documents read from stdin have no url to resolve against
+        let preview = collect(html, None);
+        assert_eq!(preview.social().open_graph().image_url(), None);
+        assert_eq!(
+            messages(&preview, "relative-og-image"),
+            ["og:image ../img/cover.png is not an absolute url"]
+        );
+    }
+}
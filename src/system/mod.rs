@@ -1,3 +1,4 @@
+use crate::error::{Error, ErrorKind};
 use crate::logging::SYSTEM;
 use crate::service::{IntoRequest, IntoResponse, Request, Response};
 use std::fmt::{Debug, Display};
@@ -22,15 +23,15 @@ impl<App> System<App> {
     }
 
     /// Runs specified request through current service
-    pub async fn run<T, B>(mut self, request: T)
+    pub async fn run<T, B>(mut self, request: T) -> Result<Response<B>, App::Error>
     where
         App: Service<Request<T>, Response = Response<B>>,
         T: IntoRequest<T>,
-        B: IntoResponse<B> + Debug,
-        App::Error: Debug + Display,
+        B: IntoResponse<B> + Debug + Send + 'static,
+        App::Error: Debug + Display + From<Error> + Send + 'static,
         App::Future: Send + 'static
     {
-        loop {
+        let res = loop {
             let app = match self.app.ready().await {
                 Err(_err) => {
                     event!(target: SYSTEM, Level::WARN, "system is busy...");
@@ -44,16 +45,18 @@ impl<App> System<App> {
 
             let handle = tokio::spawn(async move {
                 event!(target: SYSTEM, Level::DEBUG, "processing request...");
-                match fut.await {
+                let res = fut.await;
+                match &res {
                     Ok(res) => event!(target: SYSTEM, Level::INFO, "{:?}", res),
                     Err(err) => event!(target: SYSTEM, Level::ERROR, "error: {}", err),
                 }
+                res
             });
 
             match handle.await {
-                Ok(_) => {
+                Ok(res) => {
                     event!(target: SYSTEM, Level::DEBUG, "request complete");
-                    break;
+                    break res;
                 }
                 Err(err) => {
                     event!(
@@ -62,10 +65,11 @@ impl<App> System<App> {
                         "error processing request; {:?}",
                         err
                     );
-                    break;
+                    break Err(App::Error::from(Error::from(ErrorKind::Failed)));
                 }
             }
-        }
+        };
         event!(target: SYSTEM, Level::DEBUG, "shutdown");
+        res
     }
 }
@@ -9,4 +9,7 @@ pub(crate) trait Encryption {
 
 pub(crate) type Responder<T> = tokio::sync::oneshot::Sender<Result<T>>;
 
+/// Streams results back to the caller as they become available
+pub type Reporter<T> = tokio::sync::mpsc::UnboundedSender<T>;
+
 pub type Result<T> = std::result::Result<T, Error>;